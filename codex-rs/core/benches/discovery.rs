@@ -0,0 +1,104 @@
+//! Discovery over a large commands tree, compared with the walker it
+//! replaced.
+//!
+//! Run with `cargo bench -p codex-core --bench discovery`.
+
+#![allow(clippy::unwrap_used)]
+
+use std::fs;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+use codex_core::commands::CommandRoots;
+use codex_core::commands::Scope;
+use codex_core::commands::discover_custom_commands_in;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use tempfile::TempDir;
+
+const DIRS: usize = 50;
+const COMMANDS_PER_DIR: usize = 100;
+
+/// The previous walker: a `PathBuf` and a `stat` per entry, and each name
+/// rebuilt by re-splitting the file's path relative to `root`. Returns the
+/// sorted `project:name` of every command, as discovery does. Each file's
+/// first line is read too, as discovery does to look for frontmatter, so
+/// that only the walks differ.
+fn previous_discovery(root: &Path) -> Vec<String> {
+    fn command_name(root: &Path, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(root).ok()?;
+        let mut segments = relative
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<&str>>>()?;
+        let file_name = Path::new(segments.pop()?);
+        if file_name.extension()? != "md" {
+            return None;
+        }
+        segments.push(file_name.file_stem()?.to_str()?);
+        Some(segments.join("__"))
+    }
+
+    let mut commands = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if file_name.starts_with('.') || file_name.starts_with('_') {
+                continue;
+            }
+            let path: PathBuf = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if let Some(name) = command_name(root, &path) {
+                let mut first_line = String::new();
+                let _ = fs::File::open(&path)
+                    .and_then(|file| BufReader::new(file).read_line(&mut first_line));
+                commands.push(name);
+            }
+        }
+    }
+    commands.sort();
+    commands
+        .into_iter()
+        .map(|name| format!("project:{name}"))
+        .collect()
+}
+
+fn bench_discovery(c: &mut Criterion) {
+    let cwd = TempDir::new().unwrap();
+    let commands = Scope::Project.commands_dir(cwd.path());
+    for dir in 0..DIRS {
+        let dir_path = commands.join(format!("group{dir}"));
+        fs::create_dir_all(dir_path.join("_partials")).unwrap();
+        for command in 0..COMMANDS_PER_DIR {
+            fs::write(dir_path.join(format!("command{command}.md")), "").unwrap();
+        }
+        fs::write(dir_path.join("_partials/shared.md"), "").unwrap();
+    }
+    let roots = CommandRoots::new(cwd.path(), None);
+    let discovered = discover_custom_commands_in(&roots);
+    assert_eq!(discovered.len(), DIRS * COMMANDS_PER_DIR);
+    assert_eq!(discovered, previous_discovery(&commands));
+
+    let mut group = c.benchmark_group("discover 5000 commands");
+    group.bench_function("previous walker", |b| {
+        b.iter(|| previous_discovery(&commands))
+    });
+    group.bench_function("discover_custom_commands_in", |b| {
+        b.iter(|| discover_custom_commands_in(&roots))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_discovery);
+criterion_main!(benches);
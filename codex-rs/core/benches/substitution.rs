@@ -0,0 +1,72 @@
+//! Placeholder substitution in a large body with many placeholders, compared
+//! with replacing one placeholder after another as substitution used to.
+//!
+//! Run with `cargo bench -p codex-core --bench substitution`.
+
+#![allow(clippy::unwrap_used)]
+
+use std::path::Path;
+
+use codex_core::commands::ExpandOptions;
+use codex_core::commands::substitute_placeholders;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+
+/// Repetitions of a line using every kind of placeholder.
+const LINES: usize = 10_000;
+
+/// The previous approach: one `replace` over the whole body per placeholder,
+/// longest names first so that `$ARGUMENTS_FROM_2` and `$10` are not taken
+/// for `$ARGUMENTS` and `$1`. Only the placeholders of the benchmarked body
+/// are handled, and values must be plain words, which need no shell quoting.
+fn replace_each(body: &str, args: &str, cwd: &Path, options: &ExpandOptions) -> String {
+    let positional: Vec<&str> = args.split_whitespace().collect();
+    let mut out = body.to_string();
+    for (index, arg) in positional.iter().enumerate().rev() {
+        let n = index + 1;
+        out = out
+            .replace(&format!("${{{n}:shellquote}}"), arg)
+            .replace(
+                &format!("$ARGUMENTS_FROM_{n}"),
+                &positional[index..].join(" "),
+            )
+            .replace(&format!("${n}"), arg);
+    }
+    out = out
+        .replace("${ARGUMENTS:shellquote}", args)
+        .replace("$ARGUMENTS", args)
+        .replace("$INPUT", options.input.as_deref().unwrap_or(""))
+        .replace("$CWD", &cwd.to_string_lossy())
+        .replace("$SELECTION", options.selection.as_deref().unwrap_or(""))
+        .replace("$OS", std::env::consts::OS);
+    out.replace("${CODEX_BENCH_UNSET}", "")
+}
+
+fn bench_substitution(c: &mut Criterion) {
+    let line = "Fix $1 in $CWD ($ARGUMENTS_FROM_2) for $INPUT: ${2:shellquote} $ARGUMENTS \
+                ${CODEX_BENCH_UNSET} $SELECTION $OS $$ costs $ 5\n";
+    let body = line.repeat(LINES);
+    let args = "src/lib.rs the failing tests";
+    let options = ExpandOptions {
+        input: Some(format!("/project:fix {args}")),
+        selection: Some("let x = 1;".to_string()),
+        ..Default::default()
+    };
+    let cwd = Path::new("/work/repo");
+    let substituted = substitute_placeholders(&body, args, cwd, &options).unwrap();
+    assert!(!substituted.contains("$1"));
+    assert_eq!(substituted, replace_each(&body, args, cwd, &options));
+
+    let mut group = c.benchmark_group("substitute 10000 lines of placeholders");
+    group.bench_function("replace each placeholder", |b| {
+        b.iter(|| replace_each(&body, args, cwd, &options))
+    });
+    group.bench_function("substitute_placeholders", |b| {
+        b.iter(|| substitute_placeholders(&body, args, cwd, &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_substitution);
+criterion_main!(benches);
@@ -0,0 +1,279 @@
+//! Commands read from a `commands.zip` archive, for distributing a command
+//! pack as one file. Enabled by the `commands-archive` feature.
+//!
+//! A commands directory that does not exist is replaced by the archive next
+//! to it, e.g. `.codex/commands.zip` for `.codex/commands/`; when both
+//! exist, the directory wins and the archive is ignored. Archive entries are
+//! laid out like the directory (including a `config.toml` at the top level)
+//! and are named by the same rules. Partials cannot be included from an
+//! archive: `@include` fails and `@include-if` is skipped.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::COMMAND_FILE_EXTENSION;
+use super::ExpandError;
+use super::FoundCommand;
+use super::command_key;
+use super::config_file::CONFIG_FILE_NAME;
+use super::config_file::CommandsConfig;
+use super::config_file::parse_commands_config;
+use super::discover::relative_command_name;
+use super::frontmatter::FrontmatterFlags;
+use super::frontmatter::frontmatter_flags;
+use super::name_segments;
+use super::normalize_contents;
+
+/// Extension of the archive that stands in for a commands directory.
+const ARCHIVE_EXTENSION: &str = "zip";
+
+/// A commands archive, read into memory.
+pub(super) struct CommandArchive {
+    /// Path of the archive file. Commands in it are reported as paths
+    /// beneath it, e.g. `.codex/commands.zip/review/security.md`.
+    path: PathBuf,
+    /// File contents by `/`-separated path relative to the archive root.
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl CommandArchive {
+    /// The archive for the commands directory `root`, or `None` when `root`
+    /// exists or there is no archive next to it.
+    pub(super) fn for_root(root: &Path) -> Result<Option<Self>, ExpandError> {
+        if root.exists() {
+            return Ok(None);
+        }
+        let path = root.with_extension(ARCHIVE_EXTENSION);
+        match std::fs::File::open(&path) {
+            Ok(file) => Self::from_reader(path, file).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ExpandError::Io(path, e)),
+        }
+    }
+
+    /// Read the archive file at `path`.
+    pub(super) fn open(path: &Path) -> Result<Self, ExpandError> {
+        let file = std::fs::File::open(path).map_err(|e| ExpandError::Io(path.to_path_buf(), e))?;
+        Self::from_reader(path.to_path_buf(), file)
+    }
+
+    /// Read every file in the archive `reader`, which is reported as `path`.
+    pub(super) fn from_reader(
+        path: PathBuf,
+        reader: impl Read + Seek,
+    ) -> Result<Self, ExpandError> {
+        let mut archive = zip::ZipArchive::new(reader)
+            .map_err(|e| ExpandError::InvalidArchive(path.clone(), e))?;
+        let mut files = BTreeMap::new();
+        for index in 0..archive.len() {
+            let mut entry = archive
+                .by_index(index)
+                .map_err(|e| ExpandError::InvalidArchive(path.clone(), e))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .map_err(|e| ExpandError::Io(path.join(&name), e))?;
+            files.insert(name, contents);
+        }
+        Ok(Self { path, files })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Settings from the archive's `config.toml`, or the defaults.
+    fn config(&self) -> Result<CommandsConfig, ExpandError> {
+        match self.files.get(CONFIG_FILE_NAME) {
+            Some(contents) => {
+                let path = self.path.join(CONFIG_FILE_NAME);
+                let contents = std::str::from_utf8(contents)
+                    .map_err(|e| ExpandError::Io(path.clone(), invalid_data(e)))?;
+                parse_commands_config(&path, contents)
+            }
+            None => Ok(CommandsConfig::default()),
+        }
+    }
+
+    /// The commands in the archive, sorted by name, ties broken as in
+    /// discovery of a directory: the entry the name resolves to first.
+    pub(super) fn commands(&self) -> Vec<(String, PathBuf)> {
+        let mut commands: Vec<(String, &str)> = self
+            .files
+            .keys()
+            .filter_map(|entry| Some((relative_command_name(Path::new(entry))?, entry.as_str())))
+            .collect();
+        commands.sort_by(|a, b| {
+            let is_canonical = |(name, entry): &(String, &str)| entry_name(name) == *entry;
+            a.0.cmp(&b.0)
+                .then_with(|| is_canonical(b).cmp(&is_canonical(a)))
+        });
+        commands
+            .into_iter()
+            .map(|(name, entry)| (name, self.path.join(entry)))
+            .collect()
+    }
+
+    /// The frontmatter flags of the command at `path`, one of
+    /// [`commands`](Self::commands).
+    pub(super) fn frontmatter_flags(&self, path: &Path) -> FrontmatterFlags {
+        path.strip_prefix(&self.path)
+            .ok()
+            .and_then(Path::to_str)
+            .and_then(|entry| {
+                self.files
+                    .get(&entry.replace(std::path::MAIN_SEPARATOR, "/"))
+            })
+            .and_then(|contents| std::str::from_utf8(contents).ok())
+            .map(|contents| frontmatter_flags(contents.trim_start_matches('\u{feff}')))
+            .unwrap_or_default()
+    }
+
+    /// Look up the command `name`, matched case-insensitively like in a
+    /// directory. `name` must already have been validated.
+    pub(super) fn find(&self, name: &str) -> Result<Option<FoundCommand>, ExpandError> {
+        let exact = entry_name(name);
+        let entry = if self.files.contains_key(&exact) {
+            Some(exact)
+        } else {
+            let key = command_key(name);
+            self.commands()
+                .into_iter()
+                .find(|(candidate, _)| command_key(candidate) == key)
+                .and_then(|(_, path)| {
+                    let entry = path.strip_prefix(&self.path).ok()?.to_str()?;
+                    Some(entry.replace(std::path::MAIN_SEPARATOR, "/"))
+                })
+        };
+        let Some((entry, contents)) = entry.and_then(|entry| self.files.get_key_value(&entry))
+        else {
+            return Ok(None);
+        };
+
+        let config = self.config()?;
+        let path = self.path.join(entry);
+        if contents.len() as u64 > config.max_file_bytes {
+            return Err(ExpandError::FileTooLarge(path, config.max_file_bytes));
+        }
+        let contents = String::from_utf8(contents.clone())
+            .map(normalize_contents)
+            .map_err(|e| ExpandError::Io(path.clone(), invalid_data(e)))?;
+        Ok(Some(FoundCommand {
+            root: self.path.clone(),
+            config,
+            path,
+            contents,
+            on_disk: false,
+        }))
+    }
+}
+
+/// The archive entry a command name maps to, e.g. `review/security.md`.
+fn entry_name(name: &str) -> String {
+    format!("{}.{COMMAND_FILE_EXTENSION}", name_segments(name).join("/"))
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::CommandRoots;
+    use crate::commands::Scope;
+    use crate::commands::discover::discover_commands;
+    use crate::commands::expand_custom_command_in;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    fn archive_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_commands_from_an_in_memory_archive() {
+        let bytes = archive_bytes(&[
+            ("config.toml", "max_file_bytes = 16\n"),
+            ("Review/Security.md", "Audit $ARGUMENTS"),
+            ("review/_partial.md", "partial"),
+            ("huge.md", "longer than sixteen bytes"),
+        ]);
+        let archive =
+            CommandArchive::from_reader(PathBuf::from("commands.zip"), Cursor::new(bytes)).unwrap();
+
+        assert_eq!(
+            archive.commands(),
+            vec![
+                (
+                    "Review__Security".to_string(),
+                    PathBuf::from("commands.zip/Review/Security.md")
+                ),
+                ("huge".to_string(), PathBuf::from("commands.zip/huge.md")),
+            ]
+        );
+        let found = archive.find("review__security").unwrap().unwrap();
+        assert_eq!(found.contents, "Audit $ARGUMENTS");
+        assert_eq!(found.config.max_file_bytes, 16);
+        assert!(archive.find("review___partial").unwrap().is_none());
+        assert!(matches!(
+            archive.find("huge"),
+            Err(ExpandError::FileTooLarge(_, 16))
+        ));
+    }
+
+    #[test]
+    fn directory_takes_precedence_over_the_archive() {
+        let cwd = TempDir::new().unwrap();
+        let codex = cwd.path().join(".codex");
+        std::fs::create_dir_all(&codex).unwrap();
+        std::fs::write(
+            codex.join("commands.zip"),
+            archive_bytes(&[
+                ("fix.md", "archived fix $1"),
+                ("uses_partial.md", "@include _shared.md\n"),
+            ]),
+        )
+        .unwrap();
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let expanded = expand_custom_command_in("/fix it", &roots).unwrap();
+        assert_eq!(expanded.prompt, "archived fix it");
+        let commands = discover_commands(&roots);
+        assert_eq!(commands[0].scope, Scope::Project);
+        assert_eq!(commands[0].relative_path, PathBuf::from("fix.md"));
+        let err = expand_custom_command_in("/uses_partial", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+
+        write_command(cwd.path(), "lint.md", "Lint");
+        assert!(matches!(
+            expand_custom_command_in("/fix", &roots),
+            Err(ExpandError::FileNotFound(_))
+        ));
+        let names: Vec<String> = discover_commands(&roots)
+            .into_iter()
+            .map(|command| command.name)
+            .collect();
+        assert_eq!(names, vec!["lint".to_string()]);
+    }
+}
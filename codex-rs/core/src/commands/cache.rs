@@ -0,0 +1,172 @@
+//! In-memory cache of command bodies and discovery results.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::CommandRoots;
+use super::DiscoveredCommand;
+use super::MAX_COMMAND_FILE_BYTES;
+use super::Scope;
+use super::command_key;
+use super::discover::discover_commands;
+use super::read_command_file;
+
+/// Caches the contents of command files, and the commands discovered in a set
+/// of roots, so that repeated operations (such as searching on every
+/// keystroke) do not re-read every file from disk.
+///
+/// Entries are never refreshed automatically. Call
+/// [`invalidate`](Self::invalidate) or
+/// [`invalidate_name`](Self::invalidate_name) when a file changes, or create
+/// a new cache to pick up every edit.
+#[derive(Debug, Default)]
+pub struct CommandCache {
+    /// Body of each file read so far, or `None` if it could not be read
+    /// (missing, too large, not UTF-8).
+    bodies: HashMap<PathBuf, Option<String>>,
+    /// Commands discovered in each set of roots so far.
+    commands: HashMap<CommandRoots, Vec<DiscoveredCommand>>,
+}
+
+impl CommandCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the body of the command file at `path`, reading it on first
+    /// use.
+    pub(super) fn body(&mut self, path: &Path) -> Option<&str> {
+        self.bodies
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                read_command_file(path, MAX_COMMAND_FILE_BYTES)
+                    .ok()
+                    .flatten()
+            })
+            .as_deref()
+    }
+
+    /// Return the commands discovered in `roots`, walking the directories on
+    /// first use.
+    pub(super) fn commands(&mut self, roots: &CommandRoots) -> &[DiscoveredCommand] {
+        self.commands
+            .entry(roots.clone())
+            .or_insert_with(|| discover_commands(roots))
+    }
+
+    /// Discover the commands in `roots` and read their bodies ahead of time,
+    /// e.g. at startup so that the first completion or search does not wait
+    /// on the disk. Does nothing for roots already discovered, and caches an
+    /// empty result when none of the directories exists yet. The cache is
+    /// `Send`, so this can run on a background thread.
+    pub fn prewarm(&mut self, roots: &CommandRoots) {
+        if self.commands.contains_key(roots) {
+            return;
+        }
+        let paths: Vec<PathBuf> = self
+            .commands(roots)
+            .iter()
+            .map(|command| command.path.clone())
+            .collect();
+        for path in paths {
+            self.body(&path);
+        }
+    }
+
+    /// Forget the body of the file at `path`, and the discovery results of
+    /// every set of roots containing it (the file may have been created or
+    /// deleted), so that they are read again on next use.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.bodies.remove(path);
+        self.commands.retain(|roots, _| {
+            !Scope::ALL
+                .iter()
+                .any(|scope| roots.dirs(*scope).iter().any(|dir| path.starts_with(dir)))
+        });
+    }
+
+    /// Forget the body of every cached command named `name` (compared
+    /// case-insensitively) in `scope`. Only commands in discovery results
+    /// cached so far are known by name; use [`invalidate`](Self::invalidate)
+    /// for any other file.
+    pub fn invalidate_name(&mut self, scope: Scope, name: &str) {
+        let key = command_key(name);
+        for command in self.commands.values().flatten() {
+            if command.scope == scope && command.key == key {
+                self.bodies.remove(&command.path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn invalidated_path_is_read_again() {
+        let cwd = TempDir::new().unwrap();
+        let path = write_command(cwd.path(), "fix.md", "before");
+        let roots = CommandRoots::new(cwd.path(), None);
+        let mut cache = CommandCache::new();
+        assert_eq!(cache.body(&path), Some("before"));
+        assert_eq!(cache.commands(&roots).len(), 1);
+
+        write_command(cwd.path(), "fix.md", "after");
+        write_command(cwd.path(), "new.md", "");
+        assert_eq!(cache.body(&path), Some("before"));
+        assert_eq!(cache.commands(&roots).len(), 1);
+
+        cache.invalidate(&path);
+        assert_eq!(cache.body(&path), Some("after"));
+        assert_eq!(cache.commands(&roots).len(), 2);
+    }
+
+    #[test]
+    fn prewarming_fills_the_cache_once() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let fix = write_command(cwd.path(), "fix.md", "before");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+        let mut cache = CommandCache::new();
+        cache.prewarm(&roots);
+
+        write_command(cwd.path(), "fix.md", "after");
+        write_command(cwd.path(), "new.md", "");
+        cache.prewarm(&roots);
+        assert_eq!(cache.commands(&roots).len(), 1);
+        assert_eq!(cache.body(&fix), Some("before"));
+
+        let empty = TempDir::new().unwrap();
+        let missing = CommandRoots::new(&empty.path().join("gone"), None);
+        cache.prewarm(&missing);
+        assert_eq!(cache.commands(&missing).len(), 0);
+    }
+
+    #[test]
+    fn invalidated_name_is_read_again() {
+        let cwd = TempDir::new().unwrap();
+        let fix = write_command(cwd.path(), "review/Fix.md", "before");
+        let other = write_command(cwd.path(), "other.md", "other");
+        let roots = CommandRoots::new(cwd.path(), None);
+        let mut cache = CommandCache::new();
+        cache.commands(&roots);
+        assert_eq!(cache.body(&fix), Some("before"));
+        assert_eq!(cache.body(&other), Some("other"));
+
+        write_command(cwd.path(), "review/Fix.md", "after");
+        write_command(cwd.path(), "other.md", "changed");
+        cache.invalidate_name(Scope::User, "review__fix");
+        assert_eq!(cache.body(&fix), Some("before"));
+
+        cache.invalidate_name(Scope::Project, "review__fix");
+        assert_eq!(cache.body(&fix), Some("after"));
+        assert_eq!(cache.body(&other), Some("other"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Per-root defaults read from `<commands dir>/config.toml`.
+//!
+//! ```toml
+//! # Fail when a `${NAME}` placeholder names an unset environment variable.
+//! strict_env = true
+//! # Reject command files (and partials) larger than this many bytes.
+//! max_file_bytes = 65536
+//! # Reject a prompt that grows larger than this many bytes once its
+//! # `@include` and `@run` directives are expanded.
+//! max_expansion_bytes = 1048576
+//! ```
+//!
+//! The settings apply to every command in that commands directory. A command
+//! can override `strict_env` in its frontmatter. The file is not a command
+//! (it does not end in `.md`), so discovery never lists it.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::ExpandError;
+use super::MAX_COMMAND_FILE_BYTES;
+use super::MAX_EXPANSION_BYTES;
+
+pub(super) const CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct CommandsConfigToml {
+    strict_env: Option<bool>,
+    max_file_bytes: Option<u64>,
+    max_expansion_bytes: Option<u64>,
+}
+
+/// Effective settings for the commands in one commands directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct CommandsConfig {
+    pub(super) strict_env: bool,
+    pub(super) max_file_bytes: u64,
+    pub(super) max_expansion_bytes: u64,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            strict_env: false,
+            max_file_bytes: MAX_COMMAND_FILE_BYTES,
+            max_expansion_bytes: MAX_EXPANSION_BYTES,
+        }
+    }
+}
+
+/// Load the `config.toml` in `root`, falling back to the defaults when there
+/// is none.
+pub(super) fn load_commands_config(root: &Path) -> Result<CommandsConfig, ExpandError> {
+    let path = root.join(CONFIG_FILE_NAME);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(CommandsConfig::default());
+        }
+        Err(e) => return Err(ExpandError::Io(path, e)),
+    };
+    parse_commands_config(&path, &contents)
+}
+
+/// Parse the `config.toml` read from `path`.
+pub(super) fn parse_commands_config(
+    path: &Path,
+    contents: &str,
+) -> Result<CommandsConfig, ExpandError> {
+    let toml = toml::from_str::<CommandsConfigToml>(contents)
+        .map_err(|e| ExpandError::InvalidConfig(path.to_path_buf(), e))?;
+
+    let defaults = CommandsConfig::default();
+    Ok(CommandsConfig {
+        strict_env: toml.strict_env.unwrap_or(defaults.strict_env),
+        max_file_bytes: toml.max_file_bytes.unwrap_or(defaults.max_file_bytes),
+        max_expansion_bytes: toml
+            .max_expansion_bytes
+            .unwrap_or(defaults.max_expansion_bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn missing_file_uses_defaults() {
+        let root = TempDir::new().unwrap();
+        assert_eq!(
+            load_commands_config(root.path()).unwrap(),
+            CommandsConfig::default()
+        );
+    }
+
+    #[test]
+    fn settings_override_defaults() {
+        let root = TempDir::new().unwrap();
+        fs::write(
+            root.path().join(CONFIG_FILE_NAME),
+            "strict_env = true\nmax_file_bytes = 10\nmax_expansion_bytes = 20\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_commands_config(root.path()).unwrap(),
+            CommandsConfig {
+                strict_env: true,
+                max_file_bytes: 10,
+                max_expansion_bytes: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let root = TempDir::new().unwrap();
+        fs::write(root.path().join(CONFIG_FILE_NAME), "strict = true\n").unwrap();
+
+        let err = load_commands_config(root.path()).unwrap_err();
+        assert!(matches!(err, ExpandError::InvalidConfig(..)), "{err}");
+    }
+}
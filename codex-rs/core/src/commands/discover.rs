@@ -0,0 +1,1342 @@
+//! Listing the custom commands available from a working directory.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::CommandRoots;
+use super::MAX_COMMAND_FILE_BYTES;
+use super::PATH_SEPARATOR_ENCODING;
+use super::Scope;
+use super::command_file_stem;
+use super::command_key;
+use super::command_path;
+use super::escape_command_name;
+use super::frontmatter::FrontmatterFlags;
+use super::frontmatter::read_frontmatter_flags;
+use super::frontmatter::split_frontmatter;
+use super::glob::name_matches;
+use super::glob::visible_in_project;
+use super::is_valid_command_name;
+use super::read_command_file;
+
+/// A command file found by discovery. Discovery reads only the frontmatter
+/// of files, to skip `hidden` ones and ones not visible in the project, note
+/// which have frontmatter and read their `order`; anything else from their
+/// contents is read on demand (see [`DiscoveredCommand::load_summary`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredCommand {
+    pub scope: Scope,
+    /// Name used after the scope prefix, as spelled on disk, e.g.
+    /// `review__Security`. This is the name to display.
+    pub name: String,
+    /// Case-folded [`name`](Self::name), used to match commands regardless of
+    /// the case sensitivity of the filesystem, e.g. `review__security`.
+    /// Compare typed input against this rather than folding `name` again.
+    pub key: String,
+    /// Location of the command file.
+    pub path: PathBuf,
+    /// [`path`](Self::path) relative to its commands directory, as laid out
+    /// on disk, e.g. `review/Security.md`. Unlike the name, this keeps the
+    /// directory structure intact.
+    pub relative_path: PathBuf,
+    /// The top-level directory the file is in, as spelled on disk, e.g.
+    /// `review` for `review/security.md` and `review/deep/perf.md`. `None`
+    /// for files directly in the commands directory.
+    pub category: Option<String>,
+    /// Whether the file starts with a frontmatter block, e.g. for an editor
+    /// that offers to add one. An unreadable file has none.
+    pub has_frontmatter: bool,
+    /// Extension of the command file, without the `.`, e.g. `md`. Command
+    /// files are currently always Markdown, so this is `md`; it lets tools
+    /// tell file types apart should more be supported.
+    pub extension: String,
+    /// The `order` frontmatter value, for sorting curated menus with
+    /// [`by_display_order`].
+    pub order: Option<i32>,
+}
+
+impl DiscoveredCommand {
+    /// `scope:name`, i.e. the command as typed after the leading `/`. Colons
+    /// in the name are escaped as `\:`.
+    pub fn full_name(&self) -> String {
+        format!(
+            "{}:{}",
+            self.scope.as_str(),
+            escape_command_name(&self.name)
+        )
+    }
+
+    /// The name within its [`category`](Self::category), e.g. `security`
+    /// for `review__security` and `deep__perf` for `review__deep__perf`.
+    /// The whole name when there is no category.
+    pub fn name_in_category(&self) -> &str {
+        self.category
+            .as_deref()
+            .and_then(|category| self.name.strip_prefix(category))
+            .and_then(|rest| rest.strip_prefix(PATH_SEPARATOR_ENCODING))
+            .unwrap_or(&self.name)
+    }
+
+    /// Read the command file and return its summary: the first non-blank
+    /// line of the body (after any frontmatter), with Markdown heading
+    /// markers removed. `None` if the body is blank or the file cannot be
+    /// read.
+    ///
+    /// Nothing is cached, so a UI should call this only for the rows it
+    /// shows.
+    pub fn load_summary(&self) -> Option<String> {
+        load_summary(&self.path)
+    }
+}
+
+/// A directory of commands described by a `_group.md` or `README.md` file in
+/// it. Neither file is listed as a command; if both exist, `_group.md` is
+/// used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandGroup {
+    pub scope: Scope,
+    /// Prefix shared by the group's commands, without the trailing `__`,
+    /// e.g. `review` for `review__security`.
+    pub name: String,
+    /// Location of the description file.
+    pub path: PathBuf,
+}
+
+impl CommandGroup {
+    /// Read the description file and return its first non-blank line, as
+    /// [`DiscoveredCommand::load_summary`] does for commands.
+    pub fn load_description(&self) -> Option<String> {
+        load_summary(&self.path)
+    }
+}
+
+fn load_summary(path: &Path) -> Option<String> {
+    let contents = read_command_file(path, MAX_COMMAND_FILE_BYTES)
+        .ok()
+        .flatten()?;
+    summary(split_frontmatter(&contents).1)
+}
+
+/// The first non-blank line of `body`, without Markdown heading markers.
+pub(super) fn summary(body: &str) -> Option<String> {
+    body.lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Discovered commands grouped by scope, each group sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Discovered {
+    pub project: Vec<DiscoveredCommand>,
+    pub user: Vec<DiscoveredCommand>,
+    /// Described directories of every scope, project groups first, each
+    /// scope sorted by name.
+    pub groups: Vec<CommandGroup>,
+    /// Directories that exist but could not be listed, so the commands above
+    /// may be incomplete.
+    pub diagnostics: Vec<DiscoveryDiagnostic>,
+}
+
+/// A commands directory (or subdirectory, or archive) that discovery could
+/// not read, e.g. because of its permissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryDiagnostic {
+    pub path: PathBuf,
+    /// e.g. [`std::io::ErrorKind::PermissionDenied`].
+    pub kind: std::io::ErrorKind,
+    /// The error, for display.
+    pub message: String,
+}
+
+impl DiscoveryDiagnostic {
+    fn new(path: &Path, err: &std::io::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl Discovered {
+    /// The commands discovered in `scope`.
+    pub fn scope(&self, scope: Scope) -> &[DiscoveredCommand] {
+        match scope {
+            Scope::Project => &self.project,
+            Scope::User => &self.user,
+        }
+    }
+}
+
+/// Orders commands for display in a curated menu: commands with an
+/// [`order`](DiscoveredCommand::order) first, lowest first, then the rest;
+/// ties are sorted by name, then project before user. Use with
+/// [`slice::sort_by`].
+pub fn by_display_order(a: &DiscoveredCommand, b: &DiscoveredCommand) -> Ordering {
+    match (a.order, b.order) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.name.cmp(&b.name))
+    .then_with(|| a.scope.cmp(&b.scope))
+}
+
+/// List every custom command available from `cwd` as `scope:name` strings,
+/// project commands first, each scope sorted by name. The user scope is
+/// resolved against `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn discover_custom_commands(cwd: &Path) -> Vec<String> {
+    discover_custom_commands_in(&CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`discover_custom_commands`], but with an explicit home directory for
+/// the user scope (`None` disables it).
+pub fn discover_custom_commands_with_home(cwd: &Path, home: Option<&Path>) -> Vec<String> {
+    discover_custom_commands_in(&CommandRoots::new(cwd, home))
+}
+
+/// Like [`discover_custom_commands`], but listing the commands in `roots`.
+/// When several roots of a scope define the same name (compared
+/// case-insensitively), only the one from the earliest root is listed. A
+/// file that is in both scopes (through a symlink) is listed once, as a
+/// project command.
+pub fn discover_custom_commands_in(roots: &CommandRoots) -> Vec<String> {
+    discover_commands(roots)
+        .iter()
+        .map(DiscoveredCommand::full_name)
+        .collect()
+}
+
+/// Like [`discover_custom_commands_in`], but listing only the commands of
+/// `scope`, sorted by name. The other scope's directories are not read.
+pub fn discover_custom_commands_for(scope: Scope, roots: &CommandRoots) -> Vec<String> {
+    discover_scope(roots, scope, &mut Vec::new())
+        .iter()
+        .map(DiscoveredCommand::full_name)
+        .collect()
+}
+
+/// Which discovered commands to list, e.g. for a wrapper that exposes only a
+/// curated subset of the available command files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscoveryOptions {
+    /// Name patterns, e.g. `review__*` or `user:git_*`, of the commands to
+    /// list. Every command is listed when this is empty.
+    pub allow: Vec<String>,
+    /// Name patterns of the commands not to list, even when they match
+    /// [`allow`](Self::allow).
+    pub deny: Vec<String>,
+}
+
+impl DiscoveryOptions {
+    /// Whether `command` is listed with these options. A pattern with a
+    /// scope prefix is matched against the full `scope:name`, any other
+    /// against the name alone; `*` and `?` match any characters, including
+    /// the `__` of a nested command, case-insensitively.
+    pub fn allows(&self, command: &DiscoveredCommand) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|p| name_matches(p, command)))
+            && !self.deny.iter().any(|p| name_matches(p, command))
+    }
+}
+
+/// Like [`discover_custom_commands_in`], but listing only the commands that
+/// `options` allow.
+pub fn discover_custom_commands_with_options(
+    roots: &CommandRoots,
+    options: &DiscoveryOptions,
+) -> Vec<String> {
+    discover_commands(roots)
+        .iter()
+        .filter(|command| options.allows(command))
+        .map(DiscoveredCommand::full_name)
+        .collect()
+}
+
+/// Like [`discover_custom_commands_in`], but grouped by scope, with the
+/// descriptions of command directories and a diagnostic for each directory
+/// that exists but could not be read.
+pub fn discover_custom_commands_grouped(roots: &CommandRoots) -> Discovered {
+    let mut discovered = Discovered::default();
+    discovered.project = discover_scope(roots, Scope::Project, &mut discovered.diagnostics);
+    discovered.user = drop_project_files(
+        &discovered.project,
+        discover_scope(roots, Scope::User, &mut discovered.diagnostics),
+    );
+    for scope in Scope::ALL {
+        let mut seen = HashSet::new();
+        let mut groups = Vec::new();
+        for root in roots.dirs(scope) {
+            for (name, path) in collect_groups(root) {
+                if seen.insert(command_key(&name)) {
+                    groups.push(CommandGroup { scope, name, path });
+                }
+            }
+        }
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        discovered.groups.extend(groups);
+    }
+    discovered
+}
+
+/// Discover every command in every available scope, project commands first,
+/// each scope sorted by name.
+pub(super) fn discover_commands(roots: &CommandRoots) -> Vec<DiscoveredCommand> {
+    let project = discover_scope(roots, Scope::Project, &mut Vec::new());
+    let user = drop_project_files(
+        &project,
+        discover_scope(roots, Scope::User, &mut Vec::new()),
+    );
+    project.into_iter().chain(user).collect()
+}
+
+/// Drop the `user` commands whose file is also one of the `project`
+/// commands, e.g. because `~/.codex/commands` is a symlink into the
+/// project, so that each file is listed once, as a project command. Files
+/// are compared by canonical path.
+fn drop_project_files(
+    project: &[DiscoveredCommand],
+    user: Vec<DiscoveredCommand>,
+) -> Vec<DiscoveredCommand> {
+    if project.is_empty() || user.is_empty() {
+        return user;
+    }
+    let project_files: HashSet<PathBuf> = project
+        .iter()
+        .filter_map(|command| command.path.canonicalize().ok())
+        .collect();
+    user.into_iter()
+        .filter(|command| {
+            command
+                .path
+                .canonicalize()
+                .map_or(true, |path| !project_files.contains(&path))
+        })
+        .collect()
+}
+
+/// Discover the commands of `scope`, sorted by name, adding the directories
+/// that could not be read to `diagnostics`.
+pub(super) fn discover_scope(
+    roots: &CommandRoots,
+    scope: Scope,
+    diagnostics: &mut Vec<DiscoveryDiagnostic>,
+) -> Vec<DiscoveredCommand> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    let project_root = roots.project_root();
+    let project_root = project_root
+        .canonicalize()
+        .unwrap_or_else(|_| project_root.to_path_buf());
+    for root in roots.dirs(scope) {
+        let (base, listed) = list_root(root, diagnostics);
+        for (name, path, flags) in listed {
+            let key = command_key(&name);
+            // A hidden command still shadows the same name in later roots,
+            // since that is what invoking the name resolves to, and so does
+            // one that is not visible in this project.
+            if !seen.insert(key.clone())
+                || flags.hidden
+                || flags
+                    .visibility
+                    .as_ref()
+                    .is_some_and(|visibility| !visible_in_project(visibility, &project_root))
+            {
+                continue;
+            }
+            let relative_path = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            found.push(DiscoveredCommand {
+                scope,
+                name,
+                key,
+                category: category_of(&relative_path),
+                path,
+                relative_path,
+                has_frontmatter: flags.present,
+                extension,
+                order: flags.order,
+            });
+        }
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+/// The first segment of a command file's path relative to its commands
+/// directory, if the file is in a subdirectory.
+fn category_of(relative_path: &Path) -> Option<String> {
+    let mut components = relative_path.components();
+    let Some(Component::Normal(first)) = components.next() else {
+        return None;
+    };
+    components.next()?;
+    first.to_str().map(str::to_string)
+}
+
+/// The commands of the commands directory `root`, as from [`collect_sorted`]
+/// and each with its [`FrontmatterFlags`], with the path they are relative
+/// to: `root` itself, or its archive when
+/// `root` does not exist (an unreadable archive lists nothing and is added to
+/// `diagnostics`).
+fn list_root(
+    root: &Path,
+    diagnostics: &mut Vec<DiscoveryDiagnostic>,
+) -> (PathBuf, Vec<(String, PathBuf, FrontmatterFlags)>) {
+    #[cfg(feature = "commands-archive")]
+    if !root.exists() {
+        return match super::archive::CommandArchive::for_root(root) {
+            Ok(Some(archive)) => {
+                let commands = archive
+                    .commands()
+                    .into_iter()
+                    .map(|(name, path)| {
+                        let flags = archive.frontmatter_flags(&path);
+                        (name, path, flags)
+                    })
+                    .collect();
+                (archive.path().to_path_buf(), commands)
+            }
+            Ok(None) => (root.to_path_buf(), Vec::new()),
+            Err(err) => {
+                let kind = match &err {
+                    super::ExpandError::Io(_, e) => e.kind(),
+                    _ => std::io::ErrorKind::InvalidData,
+                };
+                diagnostics.push(DiscoveryDiagnostic {
+                    path: root.with_extension("zip"),
+                    kind,
+                    message: err.to_string(),
+                });
+                (root.to_path_buf(), Vec::new())
+            }
+        };
+    }
+    let commands = collect_sorted(root, diagnostics)
+        .into_iter()
+        .map(|(name, path)| {
+            let flags = read_frontmatter_flags(&path);
+            (name, path, flags)
+        })
+        .collect();
+    (root.to_path_buf(), commands)
+}
+
+/// The scope and name of the command that the file at `path` is invoked as,
+/// or `None` if it is not a command file under the project commands
+/// directory of `project_root` or the user commands directory of `home`.
+/// Hidden files and partials (and anything beneath hidden or partial
+/// directories) are not commands. This is the inverse of resolution: the
+/// name is the one discovery lists for the file.
+pub fn path_to_command_name(
+    path: &Path,
+    project_root: &Path,
+    home: &Path,
+) -> Option<(Scope, String)> {
+    [(Scope::Project, project_root), (Scope::User, home)]
+        .into_iter()
+        .find_map(|(scope, base)| {
+            let relative = path.strip_prefix(scope.commands_dir(base)).ok()?;
+            Some((scope, relative_command_name(relative)?))
+        })
+}
+
+pub(super) fn relative_command_name(relative: &Path) -> Option<String> {
+    let mut segments = Vec::new();
+    for component in relative.components() {
+        let Component::Normal(segment) = component else {
+            return None;
+        };
+        let segment = segment.to_str()?;
+        if segment.starts_with('.') || segment.starts_with('_') {
+            return None;
+        }
+        segments.push(segment);
+    }
+    let file_name = segments.pop()?;
+    let prefix = segments.join(PATH_SEPARATOR_ENCODING);
+    if is_group_description(&prefix, file_name) {
+        return None;
+    }
+    segments.push(command_file_stem(file_name)?);
+    Some(segments.join(PATH_SEPARATOR_ENCODING)).filter(|name| is_listable(name))
+}
+
+/// Whether a command named `name` can be typed back as `/scope:name` and
+/// resolve to its file. Files with other names (e.g. containing whitespace,
+/// which ends the command token) are not listed.
+fn is_listable(name: &str) -> bool {
+    is_valid_command_name(name) && !name.contains(char::is_whitespace)
+}
+
+/// Find the command file under `root` whose name matches `name`
+/// case-insensitively. If several do (only possible on a case-sensitive
+/// filesystem), the one whose on-disk name sorts first wins, as in
+/// discovery.
+pub(super) fn find_case_insensitive(root: &Path, name: &str) -> Option<PathBuf> {
+    let key = command_key(name);
+    collect_sorted(root, &mut Vec::new())
+        .into_iter()
+        .find(|(candidate, _)| command_key(candidate) == key)
+        .map(|(_, path)| path)
+}
+
+/// The commands under `root`, sorted by name. Several files can share a name
+/// (`a/b__c.md` and `a__b/c.md` are both `a__b__c`); the one expansion maps
+/// the name to comes first, so that discovery lists the file an invocation of
+/// the listed name resolves to.
+fn collect_sorted(
+    root: &Path,
+    diagnostics: &mut Vec<DiscoveryDiagnostic>,
+) -> Vec<(String, PathBuf)> {
+    let mut commands = collect_commands(root, diagnostics);
+    let is_canonical = |(name, path): &(String, PathBuf)| {
+        command_path(root, name).is_ok_and(|canonical| canonical == *path)
+    };
+    commands.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| is_canonical(b).cmp(&is_canonical(a)))
+    });
+    commands
+}
+
+/// Files that describe the directory containing them, most preferred first.
+const GROUP_DESCRIPTION_FILES: [&str; 2] = ["_group.md", "README.md"];
+
+/// Whether `file_name`, in a directory with name prefix `prefix`, is a group
+/// description. The commands root itself is not a group.
+fn is_group_description(prefix: &str, file_name: &str) -> bool {
+    !prefix.is_empty()
+        && GROUP_DESCRIPTION_FILES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(file_name))
+}
+
+/// Walk `root` and return the name and path of every command file beneath it.
+fn collect_commands(
+    root: &Path,
+    diagnostics: &mut Vec<DiscoveryDiagnostic>,
+) -> Vec<(String, PathBuf)> {
+    let mut commands = Vec::new();
+    walk(root, diagnostics, |prefix, file_name, entry| {
+        // Skip partials.
+        if file_name.starts_with('_') || is_group_description(prefix, file_name) {
+            return;
+        }
+        if let Some(stem) = command_file_stem(file_name) {
+            let name = [prefix, stem].concat();
+            if is_listable(&name) {
+                commands.push((name, entry.path()));
+            }
+        }
+    });
+    commands
+}
+
+/// Walk `root` and return the name and description file of every group
+/// beneath it. Unreadable directories are reported by command discovery, so
+/// they are ignored here.
+fn collect_groups(root: &Path) -> Vec<(String, PathBuf)> {
+    let mut groups = Vec::new();
+    walk(root, &mut Vec::new(), |prefix, file_name, entry| {
+        if is_group_description(prefix, file_name) {
+            let name = prefix
+                .strip_suffix(PATH_SEPARATOR_ENCODING)
+                .unwrap_or(prefix);
+            groups.push((name.to_string(), entry.path()));
+        }
+    });
+    // `_group.md` sorts before `README.md`, so it wins the de-duplication.
+    groups.sort_by_key(|(name, path)| (name.clone(), !path.ends_with(GROUP_DESCRIPTION_FILES[0])));
+    groups.dedup_by(|a, b| a.0 == b.0);
+    groups
+}
+
+/// Call `on_file` with the name prefix of its directory (e.g. `review__`),
+/// the file name and the entry of every file beneath `root`. Hidden entries
+/// and `_` directories are skipped. Directories that exist but cannot be
+/// listed are added to `diagnostics`.
+///
+/// Prefixes are built while descending rather than by re-splitting each
+/// file's path, and the file type reported by `read_dir` is used instead of a
+/// `stat` per entry, except for symlinks, which are followed.
+fn walk(
+    root: &Path,
+    diagnostics: &mut Vec<DiscoveryDiagnostic>,
+    mut on_file: impl FnMut(&str, &str, &std::fs::DirEntry),
+) {
+    let mut stack = vec![(root.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // A missing root just means the scope has no commands.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                diagnostics.push(DiscoveryDiagnostic::new(&dir, &e));
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            let is_dir = match entry.file_type() {
+                Ok(file_type) if !file_type.is_symlink() => file_type.is_dir(),
+                _ => entry.path().is_dir(),
+            };
+            if !is_dir {
+                on_file(&prefix, file_name, &entry);
+            } else if !file_name.starts_with('_') {
+                let prefix = [prefix.as_str(), file_name, PATH_SEPARATOR_ENCODING].concat();
+                stack.push((entry.path(), prefix));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn discovery_lists_commands_by_scope_and_skips_partials() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "_partial.md", "");
+        write_command(cwd.path(), "notes.txt", "");
+        write_command(home.path(), "hello.md", "");
+
+        let commands = discover_custom_commands_with_home(cwd.path(), Some(home.path()));
+        assert_eq!(
+            commands,
+            vec![
+                "project:fix".to_string(),
+                "project:review__security".to_string(),
+                "user:hello".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn discovery_can_be_limited_to_one_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "lint.md", "");
+        write_command(cwd.path(), "fix.md", "");
+        write_command(home.path(), "greet.md", "");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        assert_eq!(
+            discover_custom_commands_for(Scope::Project, &roots),
+            vec!["project:fix".to_string(), "project:lint".to_string()]
+        );
+        assert_eq!(
+            discover_custom_commands_for(Scope::User, &roots),
+            vec!["user:greet".to_string()]
+        );
+        assert_eq!(
+            discover_custom_commands_for(Scope::User, &roots.without_user_scope()),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn an_allowlist_limits_the_listed_commands() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "review/style.md", "");
+        write_command(home.path(), "Review_notes.md", "");
+        write_command(home.path(), "greet.md", "");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let options = DiscoveryOptions {
+            allow: vec!["review*".to_string(), "user:greet".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            discover_custom_commands_with_options(&roots, &options),
+            vec![
+                "project:review__security".to_string(),
+                "project:review__style".to_string(),
+                "user:Review_notes".to_string(),
+                "user:greet".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_denylist_removes_commands_even_when_allowed() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "deploy.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let deny = DiscoveryOptions {
+            deny: vec!["deploy".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            discover_custom_commands_with_options(&roots, &deny),
+            vec![
+                "project:fix".to_string(),
+                "project:review__security".to_string(),
+            ]
+        );
+        let both = DiscoveryOptions {
+            allow: vec!["*".to_string()],
+            deny: vec!["project:review__*".to_string(), "deploy".to_string()],
+        };
+        assert_eq!(
+            discover_custom_commands_with_options(&roots, &both),
+            vec!["project:fix".to_string()]
+        );
+    }
+
+    #[test]
+    fn grouped_discovery_puts_commands_in_their_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(home.path(), "hello.md", "");
+        write_command(home.path(), "fix.md", "");
+
+        let discovered =
+            discover_custom_commands_grouped(&CommandRoots::new(cwd.path(), Some(home.path())));
+        let names = |commands: &[DiscoveredCommand]| -> Vec<String> {
+            commands
+                .iter()
+                .map(|command| command.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names(&discovered.project),
+            vec!["fix".to_string(), "review__security".to_string()]
+        );
+        assert_eq!(
+            names(&discovered.user),
+            vec!["fix".to_string(), "hello".to_string()]
+        );
+        assert!(
+            discovered
+                .scope(Scope::User)
+                .iter()
+                .all(|command| command.scope == Scope::User)
+        );
+    }
+
+    #[test]
+    fn group_descriptions_are_found_and_not_listed() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "README.md", "Root readme is a command");
+        write_command(
+            cwd.path(),
+            "review/README.md",
+            "# Review\n\nCode review prompts",
+        );
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(
+            cwd.path(),
+            "review/deep/_group.md",
+            "Slow, thorough reviews",
+        );
+        write_command(
+            cwd.path(),
+            "review/deep/readme.md",
+            "Overridden by _group.md",
+        );
+        write_command(cwd.path(), "review/deep/perf.md", "");
+        write_command(home.path(), "notes/_group.md", "Personal notes");
+
+        let discovered =
+            discover_custom_commands_grouped(&CommandRoots::new(cwd.path(), Some(home.path())));
+        let names: Vec<&str> = discovered
+            .project
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["README", "review__deep__perf", "review__security"]
+        );
+        assert!(discovered.user.is_empty());
+
+        let groups: Vec<(Scope, &str, Option<String>)> = discovered
+            .groups
+            .iter()
+            .map(|group| (group.scope, group.name.as_str(), group.load_description()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (Scope::Project, "review", Some("Review".to_string())),
+                (
+                    Scope::Project,
+                    "review__deep",
+                    Some("Slow, thorough reviews".to_string())
+                ),
+                (Scope::User, "notes", Some("Personal notes".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_commands_are_named_and_ordered_by_name() {
+        let cwd = TempDir::new().unwrap();
+        for relative in [
+            "zeta.md",
+            "review/security.md",
+            "review/deep/perf.md",
+            "review.md",
+            "alpha/beta.md",
+            "alpha.md",
+        ] {
+            write_command(cwd.path(), relative, "");
+        }
+
+        let commands = discover_commands(&CommandRoots::new(cwd.path(), None));
+        let found: Vec<(&str, PathBuf)> = commands
+            .iter()
+            .map(|command| {
+                let relative = command
+                    .path
+                    .strip_prefix(cwd.path().join(".codex/commands"))
+                    .unwrap();
+                (command.name.as_str(), relative.to_path_buf())
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("alpha", PathBuf::from("alpha.md")),
+                ("alpha__beta", Path::new("alpha").join("beta.md")),
+                ("review", PathBuf::from("review.md")),
+                (
+                    "review__deep__perf",
+                    Path::new("review").join("deep").join("perf.md")
+                ),
+                ("review__security", Path::new("review").join("security.md")),
+                ("zeta", PathBuf::from("zeta.md")),
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_paths_match_the_on_disk_layout() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "a_/b.md", "");
+        write_command(home.path(), "hello.md", "");
+
+        let commands = discover_commands(&CommandRoots::new(cwd.path(), Some(home.path())));
+        let found: Vec<(&str, PathBuf)> = commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.relative_path.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a___b", Path::new("a_").join("b.md")),
+                ("review__security", Path::new("review").join("security.md")),
+                ("hello", PathBuf::from("hello.md")),
+            ]
+        );
+        for command in &commands {
+            assert!(command.path.ends_with(&command.relative_path));
+        }
+    }
+
+    #[test]
+    fn top_level_directories_are_categories() {
+        let cwd = TempDir::new().unwrap();
+        for relative in [
+            "fix.md",
+            "review/security.md",
+            "review/deep/perf.md",
+            "a_/b.md",
+        ] {
+            write_command(cwd.path(), relative, "");
+        }
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let commands = discover_commands(&roots);
+        let categories: Vec<(Option<&str>, &str, &str)> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.category.as_deref(),
+                    command.name_in_category(),
+                    command.name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            categories,
+            vec![
+                (Some("a_"), "b", "a___b"),
+                (None, "fix", "fix"),
+                (Some("review"), "deep__perf", "review__deep__perf"),
+                (Some("review"), "security", "review__security"),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_followed() {
+        let cwd = TempDir::new().unwrap();
+        let shared = TempDir::new().unwrap();
+        std::fs::write(shared.path().join("lint.md"), "").unwrap();
+        write_command(cwd.path(), "local.md", "");
+        std::os::unix::fs::symlink(shared.path(), cwd.path().join(".codex/commands/shared"))
+            .unwrap();
+
+        assert_eq!(
+            discover_custom_commands_with_home(cwd.path(), None),
+            vec![
+                "project:local".to_string(),
+                "project:shared__lint".to_string()
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_shared_by_both_scopes_are_listed_once() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(other.path(), "greet.md", "");
+        write_command(other.path(), "lint.md", "");
+        std::os::unix::fs::symlink(
+            other.path().join(".codex/commands/lint.md"),
+            cwd.path().join(".codex/commands/lint.md"),
+        )
+        .unwrap();
+        std::fs::create_dir_all(home.path().join(".codex")).unwrap();
+        std::os::unix::fs::symlink(
+            other.path().join(".codex/commands"),
+            home.path().join(".codex/commands"),
+        )
+        .unwrap();
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let expected = vec![
+            "project:fix".to_string(),
+            "project:lint".to_string(),
+            "user:greet".to_string(),
+        ];
+        assert_eq!(discover_custom_commands_in(&roots), expected);
+        let discovered = discover_custom_commands_grouped(&roots);
+        assert_eq!(discovered.user.len(), 1);
+        // Listing only the user scope has nothing to collapse against.
+        assert_eq!(
+            discover_custom_commands_for(Scope::User, &roots),
+            vec!["user:greet".to_string(), "user:lint".to_string()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directories_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "locked/secret.md", "");
+        let locked = cwd.path().join(".codex/commands/locked");
+        let root = cwd.path().join(".codex/commands");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        for dir in [&locked, &root] {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o000)).unwrap();
+            // Permissions do not apply to root, so there is nothing to test.
+            if std::fs::read_dir(dir).is_ok() {
+                std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+                return;
+            }
+            let discovered = discover_custom_commands_grouped(&roots);
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+            assert_eq!(discovered.diagnostics.len(), 1, "{discovered:?}");
+            let diagnostic = &discovered.diagnostics[0];
+            assert_eq!(&diagnostic.path, dir);
+            assert_eq!(diagnostic.kind, std::io::ErrorKind::PermissionDenied);
+        }
+        let discovered = discover_custom_commands_grouped(&roots);
+        assert_eq!(discovered.diagnostics, Vec::new());
+        assert_eq!(discovered.project.len(), 2);
+    }
+
+    #[test]
+    fn summaries_are_read_only_when_requested() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "original");
+        write_command(
+            cwd.path(),
+            "plan.md",
+            "---\nnext: fix\n---\n\n## Plan it\nDetails",
+        );
+        write_command(cwd.path(), "empty.md", "\n  \n");
+        write_command(cwd.path(), "gone.md", "Removed before it is shown");
+
+        let commands = discover_commands(&CommandRoots::new(cwd.path(), None));
+        // Discovery kept nothing of the bodies: changes made afterwards are
+        // what the summaries report.
+        write_command(cwd.path(), "fix.md", "\nFix the failing tests\nmore");
+        std::fs::remove_file(cwd.path().join(".codex/commands/gone.md")).unwrap();
+
+        let summaries: Vec<(&str, Option<String>)> = commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.load_summary()))
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                ("empty", None),
+                ("fix", Some("Fix the failing tests".to_string())),
+                ("gone", None),
+                ("plan", Some("Plan it".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_order_marks_are_stripped_wherever_files_are_read() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "\u{feff}Fix $ARGUMENTS");
+        write_command(
+            cwd.path(),
+            "plan.md",
+            "\u{feff}---\nnext: fix\n---\nPlan it\n@include _part.md",
+        );
+        write_command(cwd.path(), "_part.md", "\u{feff}then fix it");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let summaries: Vec<Option<String>> = discover_commands(&roots)
+            .iter()
+            .map(DiscoveredCommand::load_summary)
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                Some("Fix $ARGUMENTS".to_string()),
+                Some("Plan it".to_string())
+            ]
+        );
+        let expanded = crate::commands::expand_custom_command_in("/plan", &roots).unwrap();
+        assert_eq!(expanded.prompt, "Plan it\nthen fix it");
+        assert_eq!(expanded.next.as_deref(), Some("project:fix"));
+    }
+
+    #[test]
+    fn hidden_commands_are_not_listed_but_still_expand() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "stamp.md", "---\nhidden: true\n---\nStamp $1");
+        write_command(cwd.path(), "release.md", "Release\n@run stamp v2");
+        write_command(cwd.path(), "shown.md", "---\nhidden: false\n---\nShown");
+        write_command(home.path(), "stamp.md", "User stamp");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let names: Vec<String> = discover_commands(&roots)
+            .iter()
+            .map(DiscoveredCommand::full_name)
+            .collect();
+        // Hiding is per file: the user scope's `stamp` is listed.
+        assert_eq!(
+            names,
+            vec!["project:release", "project:shown", "user:stamp"]
+        );
+
+        let expand = |input| crate::commands::expand_custom_command_in(input, &roots);
+        assert_eq!(expand("/stamp v1").unwrap().prompt, "Stamp v1");
+        assert_eq!(expand("/release").unwrap().prompt, "Release\nStamp v2");
+    }
+
+    #[test]
+    fn discovery_reads_only_the_frontmatter() {
+        let cwd = TempDir::new().unwrap();
+        let hidden = write_command(cwd.path(), "hidden.md", "");
+        let ordered = write_command(cwd.path(), "ordered.md", "");
+        let plain = write_command(cwd.path(), "plain.md", "");
+        // Bodies that are not UTF-8 cannot be read whole, so these flags
+        // could only have come from the frontmatter.
+        std::fs::write(&hidden, b"---\nhidden: true\n---\n\xff").unwrap();
+        std::fs::write(&ordered, b"\xef\xbb\xbf+++\r\norder = 2\r\n+++\r\n\xff").unwrap();
+        std::fs::write(&plain, b"Plain\n\xff").unwrap();
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let commands = discover_commands(&roots);
+        let flags: Vec<(&str, bool, Option<i32>)> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.name.as_str(),
+                    command.has_frontmatter,
+                    command.order,
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            vec![("ordered", true, Some(2)), ("plain", false, None)]
+        );
+    }
+
+    #[test]
+    fn reports_which_commands_have_frontmatter() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "---\nnext: project:lint\n---\nFix");
+        write_command(cwd.path(), "lint.md", "Lint\n---\nnot frontmatter\n---\n");
+        write_command(cwd.path(), "plan.md", "+++\n+++\nPlan");
+
+        let flags: Vec<(String, bool)> = discover_commands(&CommandRoots::new(cwd.path(), None))
+            .into_iter()
+            .map(|command| (command.name, command.has_frontmatter))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("fix".to_string(), true),
+                ("lint".to_string(), false),
+                ("plan".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn ordered_commands_come_first_in_display_order() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "alpha.md", "Alpha");
+        write_command(cwd.path(), "deploy.md", "---\norder: 20\n---\nDeploy");
+        write_command(cwd.path(), "zeta.md", "+++\norder = -1\n+++\nZeta");
+        write_command(cwd.path(), "build.md", "---\norder: 20\n---\nBuild");
+        write_command(cwd.path(), "beta.md", "---\norder: soon\n---\nBeta");
+
+        let mut commands = discover_commands(&CommandRoots::new(cwd.path(), None));
+        commands.sort_by(by_display_order);
+        let ordered: Vec<(String, Option<i32>)> = commands
+            .into_iter()
+            .map(|command| (command.name, command.order))
+            .collect();
+        assert_eq!(
+            ordered,
+            vec![
+                ("zeta".to_string(), Some(-1)),
+                ("build".to_string(), Some(20)),
+                ("deploy".to_string(), Some(20)),
+                ("alpha".to_string(), None),
+                ("beta".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn visibility_limits_the_projects_a_command_is_listed_in() {
+        let base = TempDir::new().unwrap();
+        let base_path = base.path().canonicalize().unwrap();
+        let home = base_path.join("home");
+        let work = base_path.join("work");
+        let codex = work.join("codex");
+        let other = base_path.join("other");
+        std::fs::create_dir_all(codex.join(".git")).unwrap();
+        std::fs::create_dir_all(codex.join("src")).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        write_command(
+            &home,
+            "by_name.md",
+            "---\nvisibility: [other, cod?x]\n---\n",
+        );
+        let by_path = format!("+++\nvisibility = [\"{}/*\"]\n+++\n", work.display());
+        write_command(&home, "by_path.md", &by_path);
+        write_command(&home, "nowhere.md", "---\nvisibility: \"elsewhere\"\n---\n");
+        write_command(&home, "everywhere.md", "Everywhere");
+
+        let names = |cwd: &Path| -> Vec<String> {
+            discover_commands(&CommandRoots::new(cwd, Some(&home)))
+                .into_iter()
+                .map(|command| command.name)
+                .collect()
+        };
+        // The project is the repository, even from a subdirectory.
+        assert_eq!(
+            names(&codex.join("src")),
+            vec!["by_name", "by_path", "everywhere"]
+        );
+        assert_eq!(names(&other), vec!["by_name", "everywhere"]);
+        assert_eq!(names(&home), vec!["everywhere"]);
+
+        let roots = CommandRoots::new(&other, Some(&home));
+        let expanded = crate::commands::expand_custom_command_in("/user:nowhere", &roots);
+        assert!(expanded.is_ok());
+    }
+
+    #[test]
+    fn reports_the_extension_of_each_command_file() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "notes.v2.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "readme.md.md", "");
+        write_command(cwd.path(), "draft.prompt", "");
+
+        let commands = discover_commands(&CommandRoots::new(cwd.path(), None));
+        let extensions: Vec<(&str, &str)> = commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.extension.as_str()))
+            .collect();
+        assert_eq!(
+            extensions,
+            vec![
+                ("fix", "md"),
+                ("notes.v2", "md"),
+                ("readme.md", "md"),
+                ("review__security", "md"),
+            ]
+        );
+        // Each is the extension of its own file, not of its name.
+        for command in &commands {
+            assert_eq!(
+                command.path.extension().and_then(|e| e.to_str()),
+                Some(command.extension.as_str()),
+            );
+        }
+    }
+
+    #[test]
+    fn only_one_extension_is_stripped() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "readme.md.md", "Summarize $ARGUMENTS");
+        write_command(cwd.path(), "notes.v2.md", "");
+        write_command(cwd.path(), "draft.mdx", "");
+
+        let commands = discover_custom_commands_with_home(cwd.path(), None);
+        assert_eq!(
+            commands,
+            vec![
+                "project:notes.v2".to_string(),
+                "project:readme.md".to_string(),
+            ]
+        );
+
+        // The discovered name resolves back to the same file.
+        let expanded = crate::commands::expand_custom_command_with_home(
+            "/project:readme.md x",
+            cwd.path(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(expanded.prompt, "Summarize x");
+    }
+
+    #[test]
+    fn names_differing_only_in_case_are_listed_once() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "Fix.md", "upper");
+        write_command(cwd.path(), "fix.md", "lower");
+        write_command(cwd.path(), "Review.md", "review");
+
+        let commands = discover_commands(&CommandRoots::new(cwd.path(), None));
+        let found: Vec<(&str, &str)> = commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.key.as_str()))
+            .collect();
+        // On a case-insensitive filesystem only one of `Fix.md` and `fix.md`
+        // exists; either way a single `fix` command is listed.
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1, "fix");
+        assert_eq!(found[1], ("Review", "review"));
+    }
+
+    #[test]
+    fn lookup_matches_on_disk_name_case_insensitively() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "Review.md", "Review $ARGUMENTS");
+
+        for input in ["/project:Review a", "/project:review a", "/REVIEW a"] {
+            let expanded =
+                crate::commands::expand_custom_command_with_home(input, cwd.path(), None).unwrap();
+            assert_eq!(expanded.prompt, "Review a", "{input}");
+        }
+    }
+
+    #[test]
+    fn file_paths_map_back_to_command_names() {
+        let project = Path::new("/work/repo");
+        let home = Path::new("/home/me");
+        let name = |path: &str| path_to_command_name(Path::new(path), project, home);
+
+        assert_eq!(
+            name("/work/repo/.codex/commands/review/deep/perf.md"),
+            Some((Scope::Project, "review__deep__perf".to_string()))
+        );
+        assert_eq!(
+            name("/home/me/.codex/commands/hello.md"),
+            Some((Scope::User, "hello".to_string()))
+        );
+        for path in [
+            "/work/repo/notes/fix.md",
+            "/work/repo/.codex/commands/fix.txt",
+            "/work/repo/.codex/commands/_partial.md",
+            "/work/repo/.codex/commands/_shared/fix.md",
+            "/work/repo/.codex/commands/../fix.md",
+            "/work/repo/.codex/commands",
+            "/work/repo/.codex/commands/review/README.md",
+            "/work/repo/.codex/commands/two words.md",
+        ] {
+            assert_eq!(name(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn mapped_names_match_discovery() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(home.path(), "notes.v2.md", "");
+
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+        for command in discover_commands(&roots) {
+            assert_eq!(
+                path_to_command_name(&command.path, cwd.path(), home.path()),
+                Some((command.scope, command.name.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn missing_user_scope_is_skipped() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+
+        assert_eq!(
+            discover_custom_commands_with_home(cwd.path(), None),
+            vec!["project:fix".to_string()]
+        );
+    }
+}
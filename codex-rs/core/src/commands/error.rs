@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use super::MAX_INCLUDE_DEPTH;
+use super::MAX_NAME_COMPONENT_BYTES;
+
+/// Reasons a custom slash command could not be expanded.
+#[derive(Error, Debug)]
+pub enum ExpandError {
+    /// The input does not start with `/`.
+    #[error("input is not a slash command")]
+    NotACommand,
+
+    /// The scope before `:` is not `project` or `user`, or the scope root is
+    /// unavailable (e.g. `HOME` is unset or empty).
+    #[error("unknown command scope `{0}`")]
+    UnknownScope(String),
+
+    /// The input has a `:` with nothing before it, e.g. `/:fix`.
+    #[error("missing command scope before `:`: use `project:` or `user:`")]
+    EmptyScope,
+
+    /// The command name cannot be mapped to a file inside the scope root.
+    #[error("invalid command name `{0}`")]
+    InvalidName(String),
+
+    /// A segment of the command name would make a file or directory name
+    /// longer than filesystems allow.
+    #[error(
+        "command name has a segment longer than {} bytes",
+        MAX_NAME_COMPONENT_BYTES
+    )]
+    NameTooLong(String),
+
+    /// The `next:` frontmatter value is not a valid `scope:name` command.
+    #[error("invalid `next` command `{0}`")]
+    InvalidNextCommand(String),
+
+    /// The `extends:` frontmatter value is not a valid `scope:name` command.
+    #[error("invalid `extends` command `{0}`")]
+    InvalidExtendsCommand(String),
+
+    /// A command `extends` itself, directly or through its bases. Names the
+    /// command that would have been expanded again.
+    #[error("`extends` cycle detected at `/{0}`")]
+    ExtendsCycle(String),
+
+    /// The `deprecated_redirect:` frontmatter value is not a valid
+    /// `scope:name` command.
+    #[error("invalid `deprecated_redirect` command `{0}`")]
+    InvalidRedirectCommand(String),
+
+    /// Deprecated commands redirect to each other in a cycle. Names the
+    /// command that would have been expanded again.
+    #[error("`deprecated_redirect` cycle detected at `/{0}`")]
+    RedirectCycle(String),
+
+    /// The environment selected with `--env=` or `CODEX_COMMAND_ENV` is not
+    /// a plain name (letters, digits, `-` and `_`).
+    #[error("invalid command environment `{0}`")]
+    InvalidEnv(String),
+
+    /// The working directory the command was invoked from no longer exists
+    /// (e.g. it was removed mid-session).
+    #[error("working directory does not exist: {}", .0.display())]
+    CwdNotFound(PathBuf),
+
+    /// None of the scope's commands directories exists, so custom commands
+    /// are probably not in use at all. Names the highest-precedence one.
+    #[error("commands directory not found: {}", .0.display())]
+    NoCommandsDir(PathBuf),
+
+    /// The scope's commands directories exist but none has the command.
+    /// Names the file in the highest-precedence one.
+    #[error("command file not found: {}", .0.display())]
+    FileNotFound(PathBuf),
+
+    /// The file is larger than the size cap (the second field, in bytes).
+    #[error("command file exceeds {} bytes: {}", .1, .0.display())]
+    FileTooLarge(PathBuf, u64),
+
+    #[error("included file not found: {}", .0.display())]
+    IncludeNotFound(PathBuf),
+
+    #[error("included file is outside the commands directory: {}", .0.display())]
+    IncludeOutsideRoot(PathBuf),
+
+    /// A `@context` file is outside the project root.
+    #[error("context file is outside the project: {}", .0.display())]
+    ContextOutsideProject(PathBuf),
+
+    #[error("include cycle detected at {}", .0.display())]
+    IncludeCycle(PathBuf),
+
+    #[error("includes nested deeper than {} levels at {}", MAX_INCLUDE_DEPTH, .0.display())]
+    IncludeTooDeep(PathBuf),
+
+    /// Expanding the `@include` and `@run` directives of the file produced
+    /// more than the expansion cap (the second field, in bytes).
+    #[error("expansion of {} exceeds {} bytes", .0.display(), .1)]
+    ExpansionTooLarge(PathBuf, u64),
+
+    /// A command `@run`s itself, directly or through other commands. Names
+    /// the command that would have been run again.
+    #[error("`@run` cycle detected at `/{0}`")]
+    RunCycle(String),
+
+    #[error("`@run` nested deeper than {} levels at `/{}`", MAX_INCLUDE_DEPTH, .0)]
+    RunTooDeep(String),
+
+    /// A `--name=value` option is not accepted by the command's `args`
+    /// schema. The third field describes the accepted values.
+    #[error("invalid value `{1}` for argument `{0}`: expected {2}")]
+    InvalidArgument(String, String, String),
+
+    /// A `${NAME}` placeholder names an unset environment variable while
+    /// `strict_env` is enabled.
+    #[error("environment variable `{0}` is not set")]
+    UndefinedEnvVar(String),
+
+    /// With [`ExpandOptions::strict`](super::ExpandOptions::strict), the
+    /// placeholders that could not be resolved, in order of appearance.
+    #[error("unresolved placeholders: {}", .0.join(", "))]
+    UnresolvedPlaceholders(Vec<String>),
+
+    /// With [`ExpandOptions::strict`](super::ExpandOptions::strict), the
+    /// command at the path expanded to a prompt that is empty or only
+    /// whitespace.
+    #[error("{} expands to an empty prompt", .0.display())]
+    EmptyPrompt(PathBuf),
+
+    /// The `config.toml` of a commands directory could not be parsed.
+    #[error("invalid commands config {}: {}", .0.display(), .1)]
+    InvalidConfig(PathBuf, #[source] toml::de::Error),
+
+    /// A `commands.zip` archive could not be read.
+    #[cfg(feature = "commands-archive")]
+    #[error("invalid commands archive {}: {}", .0.display(), .1)]
+    InvalidArchive(PathBuf, #[source] zip::result::ZipError),
+
+    /// A commands directory could not be watched.
+    #[cfg(feature = "commands-watch")]
+    #[error("failed to watch {}: {}", .0.display(), .1)]
+    Watch(PathBuf, #[source] notify::Error),
+
+    #[error("failed to read {}: {}", .0.display(), .1)]
+    Io(PathBuf, #[source] std::io::Error),
+}
@@ -0,0 +1,2575 @@
+//! Turning a `/scope:name args` input into the prompt stored on disk.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::CommandRoots;
+use super::ExpandError;
+use super::FoundCommand;
+use super::MAX_INCLUDE_DEPTH;
+use super::Scope;
+use super::command_path;
+use super::config_file::CommandsConfig;
+use super::config_file::load_commands_config;
+use super::escape_command_name;
+use super::find_command;
+use super::frontmatter::FrontmatterError;
+use super::frontmatter::frontmatter_flags;
+use super::frontmatter::split_frontmatter;
+use super::frontmatter::split_frontmatter_checked;
+use super::is_valid_command_name;
+use super::lint::placeholders;
+use super::placeholders::ARGUMENTS_PLACEHOLDER;
+use super::placeholders::SELECTION_PLACEHOLDER;
+use super::placeholders::SubstitutionLog;
+use super::placeholders::substitute_tracked;
+use super::read_command_file;
+use super::schema::validate_args;
+use super::split_command_token;
+
+const INCLUDE_DIRECTIVE: &str = "@include";
+const INCLUDE_IF_DIRECTIVE: &str = "@include-if";
+const RUN_DIRECTIVE: &str = "@run";
+const CONTEXT_DIRECTIVE: &str = "@context";
+/// Marks where the prompt of the command named by `extends:` goes.
+const BASE_PLACEHOLDER: &str = "$BASE";
+/// Prefix of an include target that is resolved against the user scope.
+const USER_INCLUDE_PREFIX: &str = "~/";
+
+/// Leading argument that selects an environment variant, e.g. `--env=prod`.
+const ENV_FLAG: &str = "--env=";
+/// Environment variable selecting the variant when there is no `--env=`.
+const COMMAND_ENV_VAR: &str = "CODEX_COMMAND_ENV";
+
+/// The result of expanding a custom command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandedCommand {
+    /// Prompt to send to the model.
+    pub prompt: String,
+    /// The scope the command was found in, e.g. [`Scope::User`] for a bare
+    /// `/fix` that only the user scope has, for display next to the prompt.
+    pub scope: Scope,
+    /// Follow-up command from the `next:` frontmatter key, without the
+    /// leading `/` (e.g. `project:followup`). Offering to run it is up to the
+    /// caller.
+    pub next: Option<String>,
+    /// Non-fatal problems found while expanding.
+    pub warnings: Vec<ExpandWarning>,
+    /// The `args_prompt` frontmatter value, set only when the prompt uses
+    /// `$ARGUMENTS` and there are no arguments (including defaults). The
+    /// caller can ask the user this question and expand the command again
+    /// with the answer.
+    pub args_prompt: Option<String>,
+    /// The prompt before placeholder substitution: the body (or `template`)
+    /// without frontmatter, with includes resolved. Only set when requested
+    /// with [`ExpandOptions::include_template`], e.g. for a preview showing
+    /// the template next to what it became.
+    pub template: Option<String>,
+}
+
+/// Options that control placeholder substitution.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpandOptions {
+    /// The whole invocation line substituted for `$INPUT`, e.g.
+    /// `/project:echo hello`. `$INPUT` expands to the empty string when this
+    /// is `None`.
+    pub input: Option<String>,
+    /// Fail with [`ExpandError::UndefinedEnvVar`] when a `${NAME}`
+    /// placeholder names neither a session variable nor a set environment
+    /// variable, instead of expanding it to the empty string.
+    pub strict_env: bool,
+    /// Text substituted for `$SELECTION`, e.g. the selected part of the
+    /// caller's editor buffer. `$SELECTION` expands to the empty string when
+    /// this is `None`.
+    pub selection: Option<String>,
+    /// Also return the prompt before substitution as
+    /// [`ExpandedCommand::template`].
+    pub include_template: bool,
+    /// Session variables, e.g. a project name or ticket ID, substituted for
+    /// `${NAME}` placeholders ahead of the environment variable of the same
+    /// name.
+    pub vars: HashMap<String, String>,
+    /// Fail with [`ExpandError::UnresolvedPlaceholders`] when any placeholder
+    /// is left unresolved: a `$1`, … past the last argument, a `$NAME` that
+    /// is not a placeholder (e.g. a misspelled `$ARGUMENT`) or a `${NAME}`
+    /// that names neither a session variable nor a set environment variable.
+    /// Every such placeholder in the prompt is listed. Also fail with
+    /// [`ExpandError::EmptyPrompt`] instead of warning with
+    /// [`ExpandWarning::EmptyPrompt`].
+    pub strict: bool,
+    /// Replace an `@include` whose target does not exist with a
+    /// `[missing: target]` marker and warn with
+    /// [`ExpandWarning::MissingInclude`], so the command still runs, instead
+    /// of failing with [`ExpandError::IncludeNotFound`].
+    pub lenient_includes: bool,
+}
+
+/// Non-fatal problems reported alongside an [`ExpandedCommand`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpandWarning {
+    /// The `next:` command is well formed but does not resolve to a file.
+    NextCommandNotFound(String),
+    /// The prompt uses `$SELECTION` but no selection was provided.
+    NoSelection,
+    /// The frontmatter is malformed; the affected metadata was ignored.
+    InvalidFrontmatter(FrontmatterError),
+    /// The invoked command (the first `scope:name`) is deprecated and the
+    /// command named by its `deprecated_redirect:` (the second) was expanded
+    /// instead.
+    Deprecated(String, String),
+    /// The prompt is empty or only whitespace, e.g. because the file is
+    /// empty or has only frontmatter, so running the command does nothing.
+    EmptyPrompt,
+    /// An `@include` target does not exist and was replaced with a
+    /// `[missing: target]` marker (see [`ExpandOptions::lenient_includes`]).
+    MissingInclude(PathBuf),
+}
+
+impl fmt::Display for ExpandWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandWarning::NextCommandNotFound(next) => {
+                write!(f, "next command `/{next}` does not exist")
+            }
+            ExpandWarning::InvalidFrontmatter(error) => {
+                write!(
+                    f,
+                    "invalid frontmatter on line {}: {}",
+                    error.line, error.message
+                )
+            }
+            ExpandWarning::NoSelection => {
+                write!(f, "`$SELECTION` expanded to nothing: no text is selected")
+            }
+            ExpandWarning::Deprecated(deprecated, successor) => {
+                write!(
+                    f,
+                    "`/{deprecated}` is deprecated; use `/{successor}` instead"
+                )
+            }
+            ExpandWarning::EmptyPrompt => write!(f, "the command expanded to an empty prompt"),
+            ExpandWarning::MissingInclude(path) => {
+                write!(f, "included file `{}` does not exist", path.display())
+            }
+        }
+    }
+}
+
+/// One step of an expansion, recorded by [`expand_with_trace`] to show how
+/// a prompt came about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceStep {
+    /// `name` in `scope` resolved to the command at `path`, after applying
+    /// `--env=` and `default_args`. A command it `@run`s or `extends:` is
+    /// resolved in a step of its own.
+    CommandResolved {
+        scope: Scope,
+        name: String,
+        args: String,
+        path: PathBuf,
+    },
+    /// The frontmatter of the command at `path` was parsed, with `errors`
+    /// problems reported as warnings.
+    FrontmatterParsed { path: PathBuf, errors: usize },
+    /// An `@include` in `file` was replaced by the partial at `path`. Both
+    /// paths are canonical.
+    IncludeResolved { file: PathBuf, path: PathBuf },
+    /// An `@context` line of the command at `file` was replaced by the file
+    /// at `path`.
+    ContextInserted { file: PathBuf, path: PathBuf },
+    /// The placeholders in the prompt of the command at `path` were
+    /// substituted: `substituted` were replaced and `unresolved` distinct
+    /// ones were not.
+    PlaceholdersSubstituted {
+        path: PathBuf,
+        substituted: usize,
+        unresolved: usize,
+    },
+}
+
+/// Expand `input` (e.g. `/project:fix the tests`) into the prompt stored in
+/// the corresponding command file. The user scope is resolved against
+/// `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn expand_custom_command(input: &str, cwd: &Path) -> Result<ExpandedCommand, ExpandError> {
+    expand_custom_command_in(input, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`expand_custom_command`], but with an explicit home directory for
+/// the user scope (`None` disables it).
+pub fn expand_custom_command_with_home(
+    input: &str,
+    cwd: &Path,
+    home: Option<&Path>,
+) -> Result<ExpandedCommand, ExpandError> {
+    expand_custom_command_in(input, &CommandRoots::new(cwd, home))
+}
+
+/// Like [`expand_custom_command`], but looking commands up in `roots`. The
+/// first root of the scope that has the command wins.
+pub fn expand_custom_command_in(
+    input: &str,
+    roots: &CommandRoots,
+) -> Result<ExpandedCommand, ExpandError> {
+    expand_custom_command_with_options(input, roots, &ExpandOptions::default())
+}
+
+/// Like [`expand_custom_command_in`], with caller-provided options. Only
+/// [`selection`](ExpandOptions::selection),
+/// [`vars`](ExpandOptions::vars),
+/// [`include_template`](ExpandOptions::include_template),
+/// [`strict`](ExpandOptions::strict) and
+/// [`strict_env`](ExpandOptions::strict_env) are taken from `options`; the
+/// last applies even if the command's frontmatter or `config.toml` turns it
+/// off. `$INPUT` is always `input`.
+pub fn expand_custom_command_with_options(
+    input: &str,
+    roots: &CommandRoots,
+    options: &ExpandOptions,
+) -> Result<ExpandedCommand, ExpandError> {
+    expand_invocation(input, roots, options, None)
+}
+
+/// Like [`expand_custom_command_with_options`], but when no command file
+/// matches (including when the scope's directories do not exist), calling
+/// `fallback` with the scope and name. A body it returns is expanded as if
+/// it were the contents of the missing file, frontmatter included, except
+/// that it cannot `@include` partials. `fallback` is not called when a file
+/// matches, nor for errors other than a missing file; returning `None`
+/// keeps the original error.
+pub fn expand_custom_command_with_fallback(
+    input: &str,
+    roots: &CommandRoots,
+    options: &ExpandOptions,
+    fallback: impl Fn(Scope, &str) -> Option<String>,
+) -> Result<ExpandedCommand, ExpandError> {
+    expand_invocation(input, roots, options, Some(&fallback))
+}
+
+/// Like [`expand_custom_command_with_options`], also returning the steps of
+/// the expansion in order, as a debugging aid. The steps up to a failure are
+/// returned along with the error.
+pub fn expand_with_trace(
+    input: &str,
+    roots: &CommandRoots,
+    options: &ExpandOptions,
+) -> (Result<ExpandedCommand, ExpandError>, Vec<TraceStep>) {
+    let mut expansion = Expansion::new(roots, None);
+    expansion.trace = Some(Vec::new());
+    let result = parse_invocation(input)
+        .ok_or(ExpandError::NotACommand)
+        .and_then(|invocation| {
+            expand_resolved(
+                invocation.resolve_scope(roots)?,
+                &invocation.name,
+                invocation.args,
+                invocation.input,
+                options,
+                &mut expansion,
+            )
+        });
+    (result, expansion.trace.unwrap_or_default())
+}
+
+/// Supplies the body of a command that has no file.
+type Fallback<'a> = &'a dyn Fn(Scope, &str) -> Option<String>;
+
+fn expand_invocation(
+    input: &str,
+    roots: &CommandRoots,
+    options: &ExpandOptions,
+    fallback: Option<Fallback<'_>>,
+) -> Result<ExpandedCommand, ExpandError> {
+    let invocation = parse_invocation(input).ok_or(ExpandError::NotACommand)?;
+    let scope = invocation.resolve_scope(roots)?;
+    expand_resolved(
+        scope,
+        &invocation.name,
+        invocation.args,
+        invocation.input,
+        options,
+        &mut Expansion::new(roots, fallback),
+    )
+}
+
+/// Expand the command `name` in `scope` with `args`, without building and
+/// parsing a `/scope:name args` string. `name` is not escaped (a file
+/// `a:b.md` is the name `a:b`), but surrounding whitespace, e.g. from a
+/// copy-pasted name, is ignored. `$INPUT` expands to the equivalent
+/// invocation, e.g. `/project:fix the tests`. The user scope is resolved
+/// against `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn expand(
+    scope: Scope,
+    name: &str,
+    args: &str,
+    cwd: &Path,
+) -> Result<ExpandedCommand, ExpandError> {
+    expand_in(scope, name, args, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`expand`], but looking the command up in `roots`.
+pub fn expand_in(
+    scope: Scope,
+    name: &str,
+    args: &str,
+    roots: &CommandRoots,
+) -> Result<ExpandedCommand, ExpandError> {
+    let name = name.trim();
+    let mut input = format!("/{}:{}", scope.as_str(), escape_command_name(name));
+    if !args.is_empty() {
+        input.push(' ');
+        input.push_str(args);
+    }
+    let options = ExpandOptions::default();
+    expand_resolved(
+        scope,
+        name,
+        args,
+        &input,
+        &options,
+        &mut Expansion::new(roots, None),
+    )
+}
+
+/// Expand the file at `path` (relative to `cwd` unless absolute) as a
+/// command invoked with `args`, e.g. an ad-hoc prompt outside the commands
+/// directories. No scope is searched: the file is read directly, with the
+/// default size limit, and `--env=` and `default_args` do not apply. It is
+/// otherwise expanded like a project command, except that its `@include`
+/// targets must stay inside its directory. `$INPUT` expands to the empty
+/// string. The user scope is resolved against `$HOME` and
+/// `CODEX_COMMANDS_PATH`.
+pub fn expand_file(path: &Path, args: &str, cwd: &Path) -> Result<ExpandedCommand, ExpandError> {
+    expand_file_in(path, args, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`expand_file`], but running `@run` commands from `roots`.
+pub fn expand_file_in(
+    path: &Path,
+    args: &str,
+    roots: &CommandRoots,
+) -> Result<ExpandedCommand, ExpandError> {
+    let path = roots.cwd().join(path);
+    let config = CommandsConfig::default();
+    let contents = read_command_file(&path, config.max_file_bytes)?
+        .ok_or_else(|| ExpandError::FileNotFound(path.clone()))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut expansion = Expansion::new(roots, None);
+    expansion.file = Some(FoundCommand {
+        root: path.parent().unwrap_or(&path).to_path_buf(),
+        config,
+        path: path.clone(),
+        contents,
+        on_disk: true,
+    });
+    expand_resolved(
+        Scope::Project,
+        &name,
+        args,
+        "",
+        &ExpandOptions::default(),
+        &mut expansion,
+    )
+}
+
+/// Find the file `name` in `scope` resolves to when invoked with `args`, and
+/// the arguments to expand it with. This applies the `--env=` option (or
+/// `CODEX_COMMAND_ENV`) and the command's `default_args`. `fallback`, if
+/// any, supplies the command when there is no file.
+pub(super) fn resolve_command(
+    scope: Scope,
+    name: &str,
+    args: &str,
+    roots: &CommandRoots,
+    fallback: Option<Fallback<'_>>,
+) -> Result<(FoundCommand, String), ExpandError> {
+    // Otherwise a deleted working directory would surface as a confusing
+    // `FileNotFound` for every project command.
+    if !roots.cwd().is_dir() {
+        return Err(ExpandError::CwdNotFound(roots.cwd().to_path_buf()));
+    }
+    let (env, user_args) = split_env_flag(args);
+    let env = env.or_else(|| {
+        std::env::var(COMMAND_ENV_VAR)
+            .ok()
+            .filter(|env| !env.is_empty())
+    });
+    let mut found = find_or_generate(roots, scope, name, env.as_deref(), fallback)?;
+
+    // Defaults come from the command the user's own selection resolved to.
+    // An `--env=` among them selects a variant only when the user did not.
+    let mut args = user_args;
+    if let Some(defaults) = split_frontmatter(&found.contents).0.default_args {
+        let (default_env, merged) = split_env_flag(&merge_default_args(&defaults, &args));
+        if let (None, Some(default_env)) = (&env, default_env) {
+            found = find_or_generate(roots, scope, name, Some(&default_env), fallback)?;
+        }
+        args = merged;
+    }
+    Ok((found, args))
+}
+
+/// State shared by a command and the commands it `@run`s.
+struct Expansion<'a> {
+    roots: &'a CommandRoots,
+    fallback: Option<Fallback<'a>>,
+    /// Paths of the commands currently being expanded, outermost first, used
+    /// to detect `@run` cycles.
+    runs: Vec<PathBuf>,
+    /// How many of `runs` the command being expanded is nested in through
+    /// `@run` or `extends`. A `deprecated_redirect` successor keeps the
+    /// depth of the command it replaces, so it can still be the outermost.
+    depth: usize,
+    /// The `max_expansion_bytes` of the outermost command, which bounds the
+    /// prompt of every command it `@run`s.
+    max_expansion_bytes: Option<u64>,
+    /// The steps taken so far, when tracing.
+    trace: Option<Vec<TraceStep>>,
+    /// A file to expand as the outermost command instead of resolving it
+    /// by name.
+    file: Option<FoundCommand>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(roots: &'a CommandRoots, fallback: Option<Fallback<'a>>) -> Self {
+        Self {
+            roots,
+            fallback,
+            runs: Vec::new(),
+            depth: 0,
+            max_expansion_bytes: None,
+            trace: None,
+            file: None,
+        }
+    }
+
+    /// Record the step `step` builds, when tracing.
+    fn record(&mut self, step: impl FnOnce() -> TraceStep) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(step());
+        }
+    }
+}
+
+fn expand_resolved(
+    scope: Scope,
+    name: &str,
+    args: &str,
+    input: &str,
+    caller_options: &ExpandOptions,
+    expansion: &mut Expansion<'_>,
+) -> Result<ExpandedCommand, ExpandError> {
+    let roots = expansion.roots;
+    let caller_args = args;
+    let (found, args) = match expansion.file.take() {
+        Some(found) => (found, args.to_string()),
+        None => resolve_command(scope, name, args, roots, expansion.fallback)?,
+    };
+    let args = args.as_str();
+    if expansion.runs.contains(&found.path) {
+        return Err(ExpandError::RunCycle(qualified_name(scope, name)));
+    }
+    if expansion.runs.len() > MAX_INCLUDE_DEPTH {
+        return Err(ExpandError::RunTooDeep(qualified_name(scope, name)));
+    }
+    expansion.record(|| TraceStep::CommandResolved {
+        scope,
+        name: name.to_string(),
+        args: args.to_string(),
+        path: found.path.clone(),
+    });
+
+    let (frontmatter, body, frontmatter_errors) = split_frontmatter_checked(&found.contents);
+    if frontmatter_flags(&found.contents).present {
+        expansion.record(|| TraceStep::FrontmatterParsed {
+            path: found.path.clone(),
+            errors: frontmatter_errors.len(),
+        });
+    }
+    if let Some(redirect) = &frontmatter.deprecated_redirect {
+        expansion.runs.push(found.path.clone());
+        let redirected = expand_redirect(
+            redirect,
+            qualified_name(scope, name),
+            caller_args,
+            input,
+            caller_options,
+            expansion,
+        );
+        expansion.runs.pop();
+        return redirected;
+    }
+    if let Some(schema) = &frontmatter.args {
+        // `--env=` is consumed while resolving, so check the given arguments
+        // as well as the ones the command is expanded with.
+        validate_args(schema, caller_args)?;
+        validate_args(schema, args)?;
+    }
+
+    let mut warnings: Vec<ExpandWarning> = frontmatter_errors
+        .into_iter()
+        .map(ExpandWarning::InvalidFrontmatter)
+        .collect();
+    let next = frontmatter
+        .next
+        .as_deref()
+        .map(|next| resolve_next(next, roots, &mut warnings))
+        .transpose()?;
+
+    // A template replaces the body, which is then only notes for authors.
+    let body = frontmatter.template.as_deref().unwrap_or(body);
+    let body = if !found.on_disk {
+        skip_includes(body, &found.path)?
+    } else {
+        let root = found
+            .root
+            .canonicalize()
+            .map_err(|e| ExpandError::Io(found.root.clone(), e))?;
+        let file = found
+            .path
+            .canonicalize()
+            .map_err(|e| ExpandError::Io(found.path.clone(), e))?;
+        let user_root = canonical_user_root(roots);
+        let mut missing = caller_options.lenient_includes.then(Vec::new);
+        let body = resolve_includes(
+            body,
+            &file,
+            &root,
+            user_root.as_deref(),
+            &found.config,
+            &mut vec![file.clone()],
+            expansion.trace.as_mut(),
+            missing.as_mut(),
+        )?;
+        warnings.extend(
+            missing
+                .into_iter()
+                .flatten()
+                .map(ExpandWarning::MissingInclude),
+        );
+        body
+    };
+    let options = ExpandOptions {
+        input: Some(input.to_string()),
+        strict_env: caller_options.strict_env
+            || frontmatter.strict_env.unwrap_or(found.config.strict_env),
+        selection: caller_options.selection.clone(),
+        include_template: false,
+        vars: caller_options.vars.clone(),
+        strict: caller_options.strict,
+        lenient_includes: caller_options.lenient_includes,
+    };
+    let raw = frontmatter.raw.unwrap_or(false);
+    if !raw
+        && options.selection.is_none()
+        && placeholders(&body).any(|(_, placeholder)| placeholder == SELECTION_PLACEHOLDER)
+    {
+        warnings.push(ExpandWarning::NoSelection);
+    }
+    let args_prompt = frontmatter
+        .args_prompt
+        .filter(|_| !raw && args.trim().is_empty() && body.contains(ARGUMENTS_PLACEHOLDER));
+    let template = caller_options.include_template.then(|| body.clone());
+
+    // Text between `@run` lines is substituted as usual; each `@run` line is
+    // replaced by the prompt of the command it names. Unresolved
+    // placeholders are collected across all of the text.
+    let log = RefCell::new(SubstitutionLog::default());
+    let substitute = |text: &str| -> Result<String, ExpandError> {
+        if raw {
+            Ok(text.to_string())
+        } else {
+            substitute_tracked(text, args, roots.cwd(), &options, &mut log.borrow_mut())
+        }
+    };
+    let max_expansion_bytes = *expansion
+        .max_expansion_bytes
+        .get_or_insert(found.config.max_expansion_bytes);
+    let child_options = ExpandOptions {
+        include_template: false,
+        ..caller_options.clone()
+    };
+    let outermost = expansion.depth == 0;
+    expansion.runs.push(found.path.clone());
+    expansion.depth += 1;
+    let base = match &frontmatter.extends {
+        Some(extends) => {
+            let base = expand_base(extends, caller_args, input, &child_options, expansion)?;
+            warnings.extend(base.warnings);
+            Some(base.prompt)
+        }
+        None => None,
+    };
+    // `$BASE` is structural like `@run`, so it is replaced even when `raw`.
+    let substitute_text = |text: &str| -> Result<String, ExpandError> {
+        let Some(base) = &base else {
+            return substitute(text);
+        };
+        let parts = split_base(text)
+            .into_iter()
+            .map(&substitute)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(parts.join(base))
+    };
+    let mut prompt = String::with_capacity(body.len());
+    if let Some(base) = base.as_deref().filter(|_| split_base(&body).len() == 1) {
+        prompt.push_str(base);
+        if !base.is_empty() && !base.ends_with('\n') {
+            prompt.push('\n');
+        }
+    }
+    let mut pending = String::new();
+    for line in body.split_inclusive('\n') {
+        if let Some(target) = parse_context(line) {
+            prompt.push_str(&substitute_text(&pending)?);
+            pending.clear();
+            // The file is inserted as is: its `$`s are not placeholders.
+            let (path, context) = read_context(&substitute(target)?, roots, &found.config)?;
+            expansion.record(|| TraceStep::ContextInserted {
+                file: found.path.clone(),
+                path,
+            });
+            prompt.push_str(&context);
+            if line.ends_with('\n') && !context.ends_with('\n') {
+                prompt.push('\n');
+            }
+            check_expansion_size(&prompt, &found.path, max_expansion_bytes)?;
+            continue;
+        }
+        let Some(run) = parse_run(line) else {
+            pending.push_str(line);
+            continue;
+        };
+        prompt.push_str(&substitute_text(&pending)?);
+        pending.clear();
+
+        let run = substitute(&run)?;
+        let invocation = parse_invocation(&run).ok_or(ExpandError::NotACommand)?;
+        let child = expand_resolved(
+            invocation.resolve_scope(roots)?,
+            &invocation.name,
+            invocation.args,
+            invocation.input,
+            &child_options,
+            expansion,
+        )?;
+        prompt.push_str(&child.prompt);
+        if line.ends_with('\n') && !child.prompt.ends_with('\n') {
+            prompt.push('\n');
+        }
+        check_expansion_size(&prompt, &found.path, max_expansion_bytes)?;
+        warnings.extend(child.warnings);
+    }
+    prompt.push_str(&substitute_text(&pending)?);
+    check_expansion_size(&prompt, &found.path, max_expansion_bytes)?;
+    expansion.runs.pop();
+    expansion.depth -= 1;
+    for transform in frontmatter.postprocess.iter().flatten() {
+        prompt = transform.apply(&prompt);
+    }
+    let log = log.into_inner();
+    if !raw {
+        expansion.record(|| TraceStep::PlaceholdersSubstituted {
+            path: found.path.clone(),
+            substituted: log.substituted,
+            unresolved: log.unresolved(),
+        });
+    }
+    if options.strict {
+        log.check()?;
+    }
+    // Only the outermost command's prompt is sent; the commands it `@run`s may
+    // well contribute nothing.
+    if outermost && prompt.trim().is_empty() {
+        if options.strict {
+            return Err(ExpandError::EmptyPrompt(found.path));
+        }
+        warnings.push(ExpandWarning::EmptyPrompt);
+    }
+
+    Ok(ExpandedCommand {
+        prompt,
+        scope,
+        next,
+        warnings,
+        args_prompt,
+        template,
+    })
+}
+
+/// `name` in `scope` as `scope:name`.
+fn qualified_name(scope: Scope, name: &str) -> String {
+    format!("{}:{}", scope.as_str(), escape_command_name(name))
+}
+
+/// Remove the first `--env=<name>` among the leading `--` options of `args`
+/// and return its name with the remaining arguments.
+fn split_env_flag(args: &str) -> (Option<String>, String) {
+    let mut env = None;
+    let mut options = Vec::new();
+    let mut rest = args;
+    while rest.starts_with("--") {
+        let (token, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match token.strip_prefix(ENV_FLAG) {
+            Some(name) if env.is_none() => env = Some(name.to_string()),
+            _ => options.push(token),
+        }
+        rest = after.trim_start();
+    }
+    if env.is_none() {
+        return (None, args.to_string());
+    }
+    if !rest.is_empty() {
+        options.push(rest);
+    }
+    (env, options.join(" "))
+}
+
+/// Prepend the `default_args` frontmatter value to `args`. A default option
+/// (`--key` or `--key=value`) is dropped when `args` has an option with the
+/// same key; every other default is kept.
+fn merge_default_args(defaults: &str, args: &str) -> String {
+    fn option_key(token: &str) -> Option<&str> {
+        let option = token
+            .strip_prefix("--")
+            .filter(|option| !option.is_empty())?;
+        Some(option.split_once('=').map_or(option, |(key, _)| key))
+    }
+
+    let overridden: Vec<&str> = args.split_whitespace().filter_map(option_key).collect();
+    let mut merged: Vec<&str> = defaults
+        .split_whitespace()
+        .filter(|token| option_key(token).is_none_or(|key| !overridden.contains(&key)))
+        .collect();
+    if !args.is_empty() {
+        merged.push(args);
+    }
+    merged.join(" ")
+}
+
+/// Find the `env` variant of `name` (`deploy.prod` for `deploy` and `prod`),
+/// falling back to `name` itself when there is no such variant or no `env`.
+fn find_variant(
+    roots: &CommandRoots,
+    scope: Scope,
+    name: &str,
+    env: Option<&str>,
+) -> Result<FoundCommand, ExpandError> {
+    if let Some(env) = env {
+        let valid = !env.is_empty()
+            && env
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ExpandError::InvalidEnv(env.to_string()));
+        }
+        // A variant name too long for the filesystem cannot exist either.
+        match find_command(roots, scope, &format!("{name}.{env}")) {
+            Err(
+                ExpandError::FileNotFound(_)
+                | ExpandError::NoCommandsDir(_)
+                | ExpandError::NameTooLong(_),
+            ) => {}
+            result => return result,
+        }
+    }
+    find_command(roots, scope, name)
+}
+
+/// Like [`find_variant`], but when no file matches, using the body from
+/// `fallback` as the command at the path the file would have in the scope's
+/// first directory.
+fn find_or_generate(
+    roots: &CommandRoots,
+    scope: Scope,
+    name: &str,
+    env: Option<&str>,
+    fallback: Option<Fallback<'_>>,
+) -> Result<FoundCommand, ExpandError> {
+    let err = match find_variant(roots, scope, name, env) {
+        Err(err @ (ExpandError::FileNotFound(_) | ExpandError::NoCommandsDir(_))) => err,
+        result => return result,
+    };
+    let (Some(contents), Some(root)) = (
+        fallback.and_then(|fallback| fallback(scope, name)),
+        roots.dirs(scope).first(),
+    ) else {
+        return Err(err);
+    };
+    Ok(FoundCommand {
+        root: root.clone(),
+        config: load_commands_config(root)?,
+        path: command_path(root, name)?,
+        contents,
+        on_disk: false,
+    })
+}
+
+/// The scope and name of a frontmatter reference to another command, e.g.
+/// `project:followup` or `/followup` (the project scope), or `None` if it
+/// does not name a command.
+fn parse_command_reference(value: &str) -> Option<(Scope, Cow<'_, str>)> {
+    let command = value.strip_prefix('/').unwrap_or(value);
+    let (scope, name) = match split_command_token(command) {
+        (Some(prefix), name) => (Scope::from_prefix(prefix)?, name),
+        (None, name) => (Scope::Project, name),
+    };
+    (is_valid_command_name(&name) && !name.contains(char::is_whitespace)).then_some((scope, name))
+}
+
+/// Split `text` at each `$BASE`. Like `$OS`, it is not matched when followed
+/// by a letter, digit or `_`, so `$BASELINE` is left alone.
+fn split_base(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (offset, _) in text.match_indices(BASE_PLACEHOLDER) {
+        let end = offset + BASE_PLACEHOLDER.len();
+        if text[end..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+        parts.push(&text[start..offset]);
+        start = end;
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Expand the command named by an `extends:` value as the base of the
+/// command invoked with `args` and `input`.
+fn expand_base(
+    value: &str,
+    args: &str,
+    input: &str,
+    options: &ExpandOptions,
+    expansion: &mut Expansion<'_>,
+) -> Result<ExpandedCommand, ExpandError> {
+    let (scope, name) = parse_command_reference(value)
+        .ok_or_else(|| ExpandError::InvalidExtendsCommand(value.to_string()))?;
+    let (base, _) = resolve_command(scope, &name, args, expansion.roots, expansion.fallback)?;
+    if expansion.runs.contains(&base.path) {
+        return Err(ExpandError::ExtendsCycle(qualified_name(scope, &name)));
+    }
+    expand_resolved(scope, &name, args, input, options, expansion)
+}
+
+/// Expand the command named by the `deprecated_redirect:` value of the
+/// command `deprecated` in its place, with the same `args` and `input`.
+fn expand_redirect(
+    value: &str,
+    deprecated: String,
+    args: &str,
+    input: &str,
+    options: &ExpandOptions,
+    expansion: &mut Expansion<'_>,
+) -> Result<ExpandedCommand, ExpandError> {
+    let (scope, name) = parse_command_reference(value)
+        .ok_or_else(|| ExpandError::InvalidRedirectCommand(value.to_string()))?;
+    let (successor, _) = resolve_command(scope, &name, args, expansion.roots, expansion.fallback)?;
+    if expansion.runs.contains(&successor.path) {
+        return Err(ExpandError::RedirectCycle(qualified_name(scope, &name)));
+    }
+    let mut expanded = expand_resolved(scope, &name, args, input, options, expansion)?;
+    expanded.warnings.insert(
+        0,
+        ExpandWarning::Deprecated(deprecated, qualified_name(scope, &name)),
+    );
+    Ok(expanded)
+}
+
+/// Validate a `next:` value and normalize it to `scope:name`. A command that
+/// is well formed but missing only produces a warning, since it may be
+/// created later (or live in a scope that is unavailable right now).
+fn resolve_next(
+    value: &str,
+    roots: &CommandRoots,
+    warnings: &mut Vec<ExpandWarning>,
+) -> Result<String, ExpandError> {
+    let (scope, name) = parse_command_reference(value)
+        .ok_or_else(|| ExpandError::InvalidNextCommand(value.to_string()))?;
+    let next = qualified_name(scope, &name);
+    let exists = find_command(roots, scope, &name).is_ok();
+    if !exists {
+        warnings.push(ExpandWarning::NextCommandNotFound(next.clone()));
+    }
+    Ok(next)
+}
+
+/// A parsed `/scope:name args` input.
+pub(super) struct Invocation<'a> {
+    /// The whole (trimmed) input, including the leading `/`.
+    pub(super) input: &'a str,
+    pub(super) scope: Option<&'a str>,
+    /// The command name, with `\:` escapes resolved.
+    pub(super) name: Cow<'a, str>,
+    pub(super) args: &'a str,
+}
+
+impl Invocation<'_> {
+    /// The scope named by the prefix, defaulting to the project scope.
+    pub(super) fn scope(&self) -> Result<Scope, ExpandError> {
+        match self.scope {
+            None => Ok(Scope::Project),
+            Some("") => Err(ExpandError::EmptyScope),
+            Some(prefix) => Scope::from_prefix(prefix)
+                .ok_or_else(|| ExpandError::UnknownScope(prefix.to_string())),
+        }
+    }
+
+    /// The scope to look the command up in: the one named by the prefix or,
+    /// for a bare name, the project scope, unless only the user scope has
+    /// the command.
+    pub(super) fn resolve_scope(&self, roots: &CommandRoots) -> Result<Scope, ExpandError> {
+        let scope = self.scope()?;
+        if self.scope.is_none()
+            && matches!(
+                find_command(roots, Scope::Project, &self.name),
+                Err(ExpandError::FileNotFound(_) | ExpandError::NoCommandsDir(_))
+            )
+            && find_command(roots, Scope::User, &self.name).is_ok()
+        {
+            return Ok(Scope::User);
+        }
+        Ok(scope)
+    }
+}
+
+/// Split `input` into its command token and arguments. The token ends at the
+/// first whitespace character of any kind, so a tab or a non-breaking space
+/// separates it like a space does. The arguments are the rest of the input
+/// without that leading whitespace, otherwise kept as typed.
+///
+/// Whitespace around the whole input is dropped first, so whitespace after
+/// the token alone (e.g. `/project:fix\t`) gives empty arguments, and the
+/// arguments never end in whitespace. Runs of spaces and tabs between
+/// arguments are kept in `$ARGUMENTS`; `$1`, `$2`, … split on them.
+pub(super) fn parse_invocation(input: &str) -> Option<Invocation<'_>> {
+    let input = input.trim();
+    let rest = strip_leading_noise(input.strip_prefix('/')?);
+    // Only the command token is split on `:`; colons in the arguments (such
+    // as in a URL) are left alone, whatever whitespace separates them.
+    let (token, args) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(token, args)| (token, args.trim_start()));
+    let (scope, name) = split_command_token(token);
+    Some(Invocation {
+        input,
+        scope,
+        name,
+        args,
+    })
+}
+
+/// Drop a single stray `/` or `\` typed after the leading `/`, so that
+/// `//project:fix` and `/\project:fix` behave like `/project:fix`. Only one
+/// character is dropped, and only when the command token follows it
+/// directly: `///fix` and `// note` are left alone.
+fn strip_leading_noise(rest: &str) -> &str {
+    let Some(stripped) = rest.strip_prefix(['/', '\\']) else {
+        return rest;
+    };
+    match stripped.chars().next() {
+        Some(c) if !c.is_whitespace() && c != '/' && c != '\\' => stripped,
+        _ => rest,
+    }
+}
+
+/// An `@include` or `@include-if` directive.
+struct Include<'a> {
+    target: &'a str,
+    /// `@include-if`: a missing target is skipped instead of being an error.
+    optional: bool,
+}
+
+/// Parse an `@include <path>` or `@include-if <path>` line.
+fn parse_include(line: &str) -> Option<Include<'_>> {
+    let line = line.trim();
+    let (rest, optional) = match line.strip_prefix(INCLUDE_IF_DIRECTIVE) {
+        Some(rest) => (rest, true),
+        None => (line.strip_prefix(INCLUDE_DIRECTIVE)?, false),
+    };
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = rest.trim();
+    (!target.is_empty()).then_some(Include { target, optional })
+}
+
+/// Parse a `@context <path>` line into its path.
+fn parse_context(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(CONTEXT_DIRECTIVE)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim()).filter(|target| !target.is_empty())
+}
+
+/// Read the `@context` file `target`, which is relative to the working
+/// directory of `roots` and must stay inside its project root, returning its
+/// canonical path and contents.
+fn read_context(
+    target: &str,
+    roots: &CommandRoots,
+    config: &CommandsConfig,
+) -> Result<(PathBuf, String), ExpandError> {
+    let path = roots.cwd().join(target);
+    let canonical = match path.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ExpandError::IncludeNotFound(path));
+        }
+        Err(e) => return Err(ExpandError::Io(path, e)),
+    };
+    let project_root = roots.project_root();
+    let project_root = project_root
+        .canonicalize()
+        .map_err(|e| ExpandError::Io(project_root.to_path_buf(), e))?;
+    if !canonical.starts_with(&project_root) {
+        return Err(ExpandError::ContextOutsideProject(path));
+    }
+    let contents = read_command_file(&canonical, config.max_file_bytes)?
+        .ok_or(ExpandError::IncludeNotFound(path))?;
+    Ok((canonical, contents))
+}
+
+/// Parse a `@run <command> [args]` line into the invocation it names, with a
+/// leading `/` (e.g. `/project:lint src/`), or `None` if the line is not a
+/// `@run` directive.
+fn parse_run(line: &str) -> Option<String> {
+    let rest = line.trim().strip_prefix(RUN_DIRECTIVE)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let command = rest.trim();
+    let command = command.strip_prefix('/').unwrap_or(command);
+    (!command.is_empty()).then(|| format!("/{command}"))
+}
+
+/// Recursively inline `@include` directives in `body`, which was read from
+/// `file` in the commands directory `root`. A target starting with `~/` is
+/// resolved against `user_root` instead, the canonical path of the user
+/// scope's first commands directory, and must stay inside it; with no
+/// `user_root` it does not exist. Partials larger than the `config`'s
+/// `max_file_bytes` are rejected, as is a result larger than its
+/// `max_expansion_bytes`. `stack` holds the canonical paths of the files
+/// currently being expanded and is used to detect cycles. Each partial
+/// inlined is recorded in `trace`, if given. When `missing` is given, a
+/// target that does not exist is replaced with a `[missing: target]` marker
+/// and its path pushed to `missing` instead of failing.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn resolve_includes(
+    body: &str,
+    file: &Path,
+    root: &Path,
+    user_root: Option<&Path>,
+    config: &CommandsConfig,
+    stack: &mut Vec<PathBuf>,
+    mut trace: Option<&mut Vec<TraceStep>>,
+    mut missing: Option<&mut Vec<PathBuf>>,
+) -> Result<String, ExpandError> {
+    let mut out = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        let Some(include) = parse_include(line) else {
+            out.push_str(line);
+            continue;
+        };
+
+        let (path, root) = match include.target.strip_prefix(USER_INCLUDE_PREFIX) {
+            Some(target) => match user_root {
+                Some(user_root) => (user_root.join(target), user_root),
+                None if include.optional => continue,
+                None => {
+                    let path = include.target.into();
+                    mark_missing(&include, line, path, missing.as_deref_mut(), &mut out)?;
+                    continue;
+                }
+            },
+            None => (file.parent().unwrap_or(root).join(include.target), root),
+        };
+        let canonical = match path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && include.optional => continue,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                mark_missing(&include, line, path, missing.as_deref_mut(), &mut out)?;
+                continue;
+            }
+            Err(e) => return Err(ExpandError::Io(path, e)),
+        };
+        if !canonical.starts_with(root) {
+            return Err(ExpandError::IncludeOutsideRoot(path));
+        }
+        if stack.contains(&canonical) {
+            return Err(ExpandError::IncludeCycle(path));
+        }
+        if stack.len() > MAX_INCLUDE_DEPTH {
+            return Err(ExpandError::IncludeTooDeep(path));
+        }
+
+        let partial = read_command_file(&canonical, config.max_file_bytes)?
+            .ok_or_else(|| ExpandError::IncludeNotFound(path.clone()))?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.push(TraceStep::IncludeResolved {
+                file: file.to_path_buf(),
+                path: canonical.clone(),
+            });
+        }
+        stack.push(canonical.clone());
+        let expanded = resolve_includes(
+            &partial,
+            &canonical,
+            root,
+            user_root,
+            config,
+            stack,
+            trace.as_deref_mut(),
+            missing.as_deref_mut(),
+        )?;
+        stack.pop();
+
+        out.push_str(&expanded);
+        if line.ends_with('\n') && !expanded.ends_with('\n') {
+            out.push('\n');
+        }
+        check_expansion_size(&out, file, config.max_expansion_bytes)?;
+    }
+    Ok(out)
+}
+
+/// Append the `[missing: target]` marker that replaces the `include` on
+/// `line` to `out`, recording its `path` in `missing`, or fail when missing
+/// includes are not being collected.
+fn mark_missing(
+    include: &Include<'_>,
+    line: &str,
+    path: PathBuf,
+    missing: Option<&mut Vec<PathBuf>>,
+    out: &mut String,
+) -> Result<(), ExpandError> {
+    let Some(missing) = missing else {
+        return Err(ExpandError::IncludeNotFound(path));
+    };
+    out.push_str(&format!("[missing: {}]", include.target));
+    if line.ends_with('\n') {
+        out.push('\n');
+    }
+    missing.push(path);
+    Ok(())
+}
+
+/// Fail when `expanded`, the expansion of `file` so far, exceeds `max_bytes`.
+fn check_expansion_size(expanded: &str, file: &Path, max_bytes: u64) -> Result<(), ExpandError> {
+    if expanded.len() as u64 > max_bytes {
+        return Err(ExpandError::ExpansionTooLarge(
+            file.to_path_buf(),
+            max_bytes,
+        ));
+    }
+    Ok(())
+}
+
+/// The canonical path of the user scope's first commands directory, which
+/// `~/` include targets are resolved against, or `None` when there is none.
+pub(super) fn canonical_user_root(roots: &CommandRoots) -> Option<PathBuf> {
+    roots.dirs(Scope::User).first()?.canonicalize().ok()
+}
+
+/// Drop the `@include-if` lines of `body`, a command that is not on disk
+/// (e.g. read from an archive) with the path `file`, failing on the first
+/// `@include`: such commands have no partials.
+fn skip_includes(body: &str, file: &Path) -> Result<String, ExpandError> {
+    let mut out = String::with_capacity(body.len());
+    for line in body.split_inclusive('\n') {
+        match parse_include(line) {
+            None => out.push_str(line),
+            Some(include) if include.optional => {}
+            Some(include) => {
+                let dir = file.parent().unwrap_or(file);
+                return Err(ExpandError::IncludeNotFound(dir.join(include.target)));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::expect_used, clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn expand_prompt(input: &str, cwd: &TempDir) -> Result<String, ExpandError> {
+        expand_custom_command_with_home(input, cwd.path(), None).map(|expanded| expanded.prompt)
+    }
+
+    #[test]
+    fn substitutes_arguments_in_command_body() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix $ARGUMENTS, starting with $1.");
+
+        let prompt = expand_prompt("/project:fix lint tests", &cwd).unwrap();
+        assert_eq!(prompt, "Fix lint tests, starting with lint.");
+    }
+
+    #[test]
+    fn any_unicode_whitespace_separates_name_from_arguments() {
+        for (input, name, args) in [
+            ("/fix\tthe tests", "fix", "the tests"),
+            ("/fix\u{a0}the\u{a0} tests", "fix", "the\u{a0} tests"),
+            ("/project:fix\u{3000}\t a\tb ", "fix", "a\tb"),
+        ] {
+            let invocation = parse_invocation(input).unwrap();
+            assert_eq!(invocation.name, name, "{input:?}");
+            assert_eq!(invocation.args, args, "{input:?}");
+        }
+
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix [$1] [$ARGUMENTS]");
+        assert_eq!(
+            expand_prompt("/fix\u{a0}lint\ttests", &cwd).unwrap(),
+            "Fix [lint] [lint\ttests]"
+        );
+    }
+
+    #[test]
+    fn arguments_are_trimmed_only_at_their_ends() {
+        for (input, args) in [
+            ("/project:fix", ""),
+            ("/project:fix\t", ""),
+            ("/project:fix \t \n", ""),
+            ("/project:fix\t\tlint", "lint"),
+            ("/project:fix   lint  \t tests\t", "lint  \t tests"),
+        ] {
+            let invocation = parse_invocation(input).unwrap();
+            assert_eq!(invocation.name, "fix", "{input:?}");
+            assert_eq!(invocation.args, args, "{input:?}");
+        }
+
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "[$1] [$2] [$ARGUMENTS]");
+        assert_eq!(expand_prompt("/project:fix\t", &cwd).unwrap(), "[] [] []");
+        assert_eq!(
+            expand_prompt("/project:fix \t lint   tests  ", &cwd).unwrap(),
+            "[lint] [tests] [lint   tests]"
+        );
+    }
+
+    #[test]
+    fn stray_whitespace_around_a_pasted_name_is_ignored() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix [$ARGUMENTS] $INPUT");
+
+        let invocation = parse_invocation("/project:fix   ").unwrap();
+        assert_eq!(invocation.name, "fix");
+        assert_eq!(invocation.args, "");
+        assert_eq!(
+            expand_prompt("/project:fix   ", &cwd).unwrap(),
+            "Fix [] /project:fix"
+        );
+        let expanded = expand(Scope::Project, " fix\u{a0} ", "", cwd.path()).unwrap();
+        assert_eq!(expanded.prompt, "Fix [] /project:fix");
+    }
+
+    #[test]
+    fn files_outside_the_commands_dirs_expand_directly() {
+        let cwd = TempDir::new().unwrap();
+        let prompts = TempDir::new().unwrap();
+        write_command(cwd.path(), "lint.md", "Lint $ARGUMENTS");
+        fs::write(
+            prompts.path().join("adhoc.md"),
+            "---\ndefault_args: ignored\n---\nCheck $1 [$INPUT]\n@include _note.md\n@run lint $2\n",
+        )
+        .unwrap();
+        fs::write(prompts.path().join("_note.md"), "Carefully.\n").unwrap();
+        fs::write(prompts.path().join("huge.md"), "x".repeat(300 * 1024)).unwrap();
+        fs::write(prompts.path().join("binary.md"), [0xff, 0xfe, 0x00]).unwrap();
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let expanded = expand_file_in(&prompts.path().join("adhoc.md"), "a src/", &roots).unwrap();
+        assert_eq!(expanded.prompt, "Check a []\nCarefully.\nLint src/\n");
+        fs::write(cwd.path().join("relative.md"), "Relative $1").unwrap();
+        let expanded = expand_file_in(Path::new("relative.md"), "b", &roots).unwrap();
+        assert_eq!(expanded.prompt, "Relative b");
+
+        let file = |name| expand_file_in(&prompts.path().join(name), "", &roots);
+        assert!(matches!(
+            file("huge.md"),
+            Err(ExpandError::FileTooLarge(..))
+        ));
+        assert!(matches!(file("binary.md"), Err(ExpandError::Io(..))));
+        assert!(matches!(
+            file("missing.md"),
+            Err(ExpandError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn arguments_are_never_interpreted_as_commands_or_directives() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "note.md", "Note:\n$ARGUMENTS\n");
+        write_command(cwd.path(), "other.md", "SECRET OTHER");
+        write_command(cwd.path(), "_partial.md", "SECRET PARTIAL");
+        fs::write(cwd.path().join("secret.txt"), "SECRET FILE").unwrap();
+
+        for args in [
+            "/project:other",
+            "/other $1",
+            "x\n@run other\n@include _partial.md\n@context secret.txt",
+        ] {
+            let prompt = expand_prompt(&format!("/project:note {args}"), &cwd).unwrap();
+            assert_eq!(prompt, format!("Note:\n{args}\n"));
+        }
+    }
+
+    #[test]
+    fn colons_in_arguments_do_not_affect_the_command_token() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "note.md", "Note [$1] [$ARGUMENTS]");
+
+        for (input, prompt) in [
+            (
+                "/project:note http://example.com",
+                "Note [http://example.com] [http://example.com]",
+            ),
+            (
+                "/note http://example.com",
+                "Note [http://example.com] [http://example.com]",
+            ),
+            (
+                "/note\thttp://a:8080 b",
+                "Note [http://a:8080] [http://a:8080 b]",
+            ),
+            ("/project:note\nuser:x", "Note [user:x] [user:x]"),
+        ] {
+            assert_eq!(expand_prompt(input, &cwd).unwrap(), prompt, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn structured_entry_point_skips_parsing() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "review/security.md",
+            "[$1] [$ARGUMENTS] [$INPUT]",
+        );
+        write_command(home.path(), "hello.md", "Hello $ARGUMENTS");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let expanded = expand_in(Scope::Project, "review__security", "api db", &roots).unwrap();
+        assert_eq!(
+            expanded.prompt,
+            "[api] [api db] [/project:review__security api db]"
+        );
+        let expanded = expand_in(Scope::User, "hello", "world", &roots).unwrap();
+        assert_eq!(expanded.prompt, "Hello world");
+
+        // The name is taken as is: a `/` or a scope prefix is not parsed.
+        let err = expand_in(Scope::Project, "/review__security", "", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::InvalidName(_)), "{err}");
+        let err = expand_in(Scope::Project, "user:hello", "", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn input_placeholder_expands_to_whole_invocation() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "echo.md",
+            "You typed `$INPUT` (args: $ARGUMENTS)",
+        );
+
+        let prompt = expand_prompt("  /project:echo hello  ", &cwd).unwrap();
+        assert_eq!(prompt, "You typed `/project:echo hello` (args: hello)");
+    }
+
+    #[test]
+    fn single_stray_leading_slash_is_tolerated() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix $ARGUMENTS");
+
+        for input in [
+            "/project:fix it",
+            "//project:fix it",
+            "/\\project:fix it",
+            "//fix it",
+        ] {
+            assert_eq!(expand_prompt(input, &cwd).unwrap(), "Fix it", "{input}");
+        }
+        for input in ["///project:fix it", "// fix it", "/\\\\project:fix"] {
+            let err = expand_prompt(input, &cwd).unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    ExpandError::InvalidName(_) | ExpandError::UnknownScope(_)
+                ),
+                "{input}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn bare_name_resolves_against_project_scope() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "review/security.md", "Audit $ARGUMENTS");
+
+        let prompt = expand_prompt("/review__security src/", &cwd).unwrap();
+        assert_eq!(prompt, "Audit src/");
+    }
+
+    #[test]
+    fn expansion_reports_the_resolved_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix");
+        write_command(home.path(), "fix.md", "My fix");
+        write_command(home.path(), "greet.md", "Hi");
+        let expand = |input| expand_custom_command_with_home(input, cwd.path(), Some(home.path()));
+
+        assert_eq!(expand("/fix").unwrap().scope, Scope::Project);
+        assert_eq!(expand("/user:fix").unwrap().scope, Scope::User);
+        assert_eq!(expand("/user:greet").unwrap().scope, Scope::User);
+        // A bare name falls back to the user scope.
+        assert_eq!(expand("/greet").unwrap().scope, Scope::User);
+        assert_eq!(expand("/greet").unwrap().prompt, "Hi");
+        let err = expand("/project:greet").unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn included_partial_shares_caller_arguments() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "_context.md",
+            "Target: $ARGUMENTS\nPrimary: $1, secondary: $2",
+        );
+        write_command(
+            cwd.path(),
+            "review.md",
+            "Review carefully.\n@include _context.md\nDone.",
+        );
+
+        let prompt = expand_prompt("/project:review api.rs db.rs", &cwd).unwrap();
+        assert_eq!(
+            prompt,
+            "Review carefully.\nTarget: api.rs db.rs\nPrimary: api.rs, secondary: db.rs\nDone."
+        );
+    }
+
+    #[test]
+    fn nested_includes_resolve_relative_to_including_file() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "_partials/outer.md",
+            "outer $1\n@include inner.md\n",
+        );
+        write_command(cwd.path(), "_partials/inner.md", "inner $2\n");
+        write_command(cwd.path(), "run.md", "@include _partials/outer.md\nend");
+
+        let prompt = expand_prompt("/project:run a b", &cwd).unwrap();
+        assert_eq!(prompt, "outer a\ninner b\nend");
+    }
+
+    #[test]
+    fn trace_records_each_step_of_the_expansion() {
+        let cwd = TempDir::new().unwrap();
+        let partial = write_command(cwd.path(), "_partials/outer.md", "outer $1 $2\n");
+        let command = write_command(
+            cwd.path(),
+            "run.md",
+            "---\ndescription: Run\n---\n@include _partials/outer.md\nend $ARGUMENTS",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let (result, trace) = expand_with_trace("/run a", &roots, &ExpandOptions::default());
+        assert_eq!(result.unwrap().prompt, "outer a \nend a");
+        assert_eq!(
+            trace,
+            vec![
+                TraceStep::CommandResolved {
+                    scope: Scope::Project,
+                    name: "run".to_string(),
+                    args: "a".to_string(),
+                    path: command.clone(),
+                },
+                TraceStep::FrontmatterParsed {
+                    path: command.clone(),
+                    errors: 0,
+                },
+                TraceStep::IncludeResolved {
+                    file: command.canonicalize().unwrap(),
+                    path: partial.canonicalize().unwrap(),
+                },
+                TraceStep::PlaceholdersSubstituted {
+                    path: command,
+                    substituted: 2,
+                    unresolved: 1,
+                },
+            ]
+        );
+
+        let (result, trace) = expand_with_trace("/missing", &roots, &ExpandOptions::default());
+        assert!(matches!(result, Err(ExpandError::FileNotFound(_))));
+        assert_eq!(trace, Vec::new());
+    }
+
+    #[test]
+    fn missing_positional_arguments_expand_to_empty() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_args.md", "[$1][$2]");
+        write_command(cwd.path(), "cmd.md", "@include _args.md");
+
+        assert_eq!(
+            expand_prompt("/project:cmd only", &cwd).unwrap(),
+            "[only][]"
+        );
+    }
+
+    #[test]
+    fn missing_include_is_an_error() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "cmd.md", "@include _missing.md");
+
+        let err = expand_prompt("/project:cmd", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn conditional_include_inlines_present_partial() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_local.md", "Local rules for $1.");
+        write_command(cwd.path(), "cmd.md", "Start.\n@include-if _local.md\nEnd.");
+
+        assert_eq!(
+            expand_prompt("/project:cmd api", &cwd).unwrap(),
+            "Start.\nLocal rules for api.\nEnd."
+        );
+    }
+
+    #[test]
+    fn conditional_include_skips_absent_partial() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "cmd.md", "Start.\n@include-if _local.md\nEnd.");
+
+        assert_eq!(expand_prompt("/project:cmd", &cwd).unwrap(), "Start.\nEnd.");
+    }
+
+    #[test]
+    fn include_outside_commands_root_is_rejected() {
+        let cwd = TempDir::new().unwrap();
+        fs::write(cwd.path().join("secret.md"), "secret").unwrap();
+        write_command(cwd.path(), "cmd.md", "@include ../../secret.md");
+
+        let err = expand_prompt("/project:cmd", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeOutsideRoot(_)), "{err}");
+    }
+
+    #[test]
+    fn context_files_come_from_the_project_and_includes_from_the_commands_root() {
+        let outside = TempDir::new().unwrap();
+        let repo = outside.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/lib.rs"), "fn main() { let s = \"$1\"; }\n").unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let cwd = repo.join("src");
+        write_command(&cwd, "explain.md", "Explain $1:\n@context $1\nThanks");
+        write_command(&cwd, "include.md", "@include ../../lib.rs\n");
+        write_command(&cwd, "escape.md", "@context ../../secret.txt\n");
+        let roots = CommandRoots::new(&cwd, None);
+        let expand = |input| expand_custom_command_in(input, &roots).map(|e| e.prompt);
+
+        assert_eq!(
+            expand("/explain lib.rs").unwrap(),
+            "Explain lib.rs:\nfn main() { let s = \"$1\"; }\nThanks"
+        );
+        // The same file is out of reach of `@include`.
+        let err = expand("/include").unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeOutsideRoot(_)), "{err}");
+        let err = expand("/escape").unwrap_err();
+        assert!(
+            matches!(err, ExpandError::ContextOutsideProject(_)),
+            "{err}"
+        );
+        let err = expand("/explain missing.rs").unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn tilde_includes_resolve_against_the_user_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(home.path(), "_standards.md", "Standards\n@include _more.md");
+        write_command(home.path(), "_more.md", "More $1");
+        write_command(
+            cwd.path(),
+            "review.md",
+            "Review\n@include ~/_standards.md\n",
+        );
+        write_command(cwd.path(), "optional.md", "A\n@include-if ~/_absent.md\nB");
+        fs::write(home.path().join(".codex/secret.md"), "secret").unwrap();
+        write_command(cwd.path(), "escape.md", "@include ~/../secret.md");
+
+        let expand = |input| {
+            expand_custom_command_with_home(input, cwd.path(), Some(home.path()))
+                .map(|expanded| expanded.prompt)
+        };
+        assert_eq!(expand("/review x").unwrap(), "Review\nStandards\nMore x\n");
+        assert_eq!(expand("/optional").unwrap(), "A\nB");
+        let err = expand("/escape").unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeOutsideRoot(_)), "{err}");
+
+        let err = expand_prompt("/review", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn lenient_includes_mark_missing_partials_and_warn() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_nested.md", "Nested\n@include _gone.md\n");
+        write_command(
+            cwd.path(),
+            "review.md",
+            "Review $1\n@include _absent.md\n@include _nested.md\n@include ~/_home.md\nEnd",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+        let lenient = ExpandOptions {
+            lenient_includes: true,
+            ..Default::default()
+        };
+
+        let expanded = expand_custom_command_with_options("/review x", &roots, &lenient).unwrap();
+        assert_eq!(
+            expanded.prompt,
+            "Review x\n[missing: _absent.md]\nNested\n[missing: _gone.md]\n\
+             [missing: ~/_home.md]\nEnd"
+        );
+        let commands = cwd.path().join(".codex/commands").canonicalize().unwrap();
+        assert_eq!(
+            expanded.warnings,
+            vec![
+                ExpandWarning::MissingInclude(commands.join("_absent.md")),
+                ExpandWarning::MissingInclude(commands.join("_gone.md")),
+                ExpandWarning::MissingInclude(PathBuf::from("~/_home.md")),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_includes_fail_by_default() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "review.md", "Review\n@include _absent.md\n");
+
+        let err = expand_prompt("/review", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+        let strict = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let roots = CommandRoots::new(cwd.path(), None);
+        let err = expand_custom_command_with_options("/review", &roots, &strict).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn include_cycle_is_detected() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_a.md", "@include _b.md");
+        write_command(cwd.path(), "_b.md", "@include _a.md");
+        write_command(cwd.path(), "cmd.md", "@include _a.md");
+
+        let err = expand_prompt("/project:cmd", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeCycle(_)), "{err}");
+    }
+
+    #[test]
+    fn run_inlines_another_command_with_its_own_frontmatter() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "release.md",
+            "Release $1.\n@run project:lint $1\n@run user:notes\nDone.",
+        );
+        write_command(
+            cwd.path(),
+            "lint.md",
+            "---\ndefault_args: --strict\nnext: project:nope\n---\nLint [$ARGUMENTS] via $INPUT",
+        );
+        write_command(home.path(), "notes.md", "@run /project:stamp\n");
+        write_command(cwd.path(), "stamp.md", "Stamp [$1]");
+
+        let expanded =
+            expand_custom_command_with_home("/release v2", cwd.path(), Some(home.path())).unwrap();
+        assert_eq!(
+            expanded.prompt,
+            "Release v2.\nLint [--strict v2] via /project:lint v2\nStamp []\nDone."
+        );
+        assert_eq!(expanded.next, None);
+        assert_eq!(
+            expanded.warnings,
+            vec![ExpandWarning::NextCommandNotFound(
+                "project:nope".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn run_cycle_is_detected() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "a.md", "A\n@run b");
+        write_command(cwd.path(), "b.md", "B\n@run project:A x");
+        write_command(cwd.path(), "c.md", "@run e 1\n@run e 2");
+        write_command(cwd.path(), "d.md", "@run missing");
+        write_command(cwd.path(), "e.md", "E$1");
+
+        let err = expand_prompt("/a", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::RunCycle(name) if name == "project:A"),
+            "{err}"
+        );
+        // Running the same command twice in a row is not a cycle.
+        assert_eq!(expand_prompt("/c", &cwd).unwrap(), "E1\nE2");
+        let err = expand_prompt("/d", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn user_scope_resolves_against_home() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(home.path(), "hello.md", "Hello $ARGUMENTS");
+
+        let expanded =
+            expand_custom_command_with_home("/user:hello world", cwd.path(), Some(home.path()))
+                .unwrap();
+        assert_eq!(expanded.prompt, "Hello world");
+    }
+
+    #[test]
+    fn template_is_returned_before_substitution_on_request() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_scope.md", "Only touch $1.");
+        write_command(
+            cwd.path(),
+            "fix.md",
+            "---\nnext: project:review\n---\nFix $ARGUMENTS.\n@include _scope.md\n",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let options = ExpandOptions {
+            include_template: true,
+            ..Default::default()
+        };
+        let expanded =
+            expand_custom_command_with_options("/fix src/lib.rs", &roots, &options).unwrap();
+        let template = expanded.template.unwrap();
+        assert_eq!(template, "Fix $ARGUMENTS.\nOnly touch $1.\n");
+        assert_eq!(expanded.prompt, "Fix src/lib.rs.\nOnly touch src/lib.rs.\n");
+        assert_eq!(
+            template
+                .replace("$ARGUMENTS", "src/lib.rs")
+                .replace("$1", "src/lib.rs"),
+            expanded.prompt
+        );
+
+        let expanded = expand_custom_command_in("/fix src/lib.rs", &roots).unwrap();
+        assert_eq!(expanded.template, None);
+    }
+
+    #[test]
+    fn session_vars_come_from_caller_options() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "ticket.md",
+            "Ticket ${TICKET} in ${HOME}\n@run tag",
+        );
+        write_command(cwd.path(), "tag.md", "Tag ${TICKET}");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let options = ExpandOptions {
+            vars: HashMap::from([
+                ("TICKET".to_string(), "ABC-1".to_string()),
+                ("HOME".to_string(), "session".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let expanded = expand_custom_command_with_options("/ticket", &roots, &options).unwrap();
+        assert_eq!(expanded.prompt, "Ticket ABC-1 in session\nTag ABC-1");
+    }
+
+    #[test]
+    fn selection_comes_from_caller_options() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "refactor.md",
+            "Refactor this ($ARGUMENTS): $SELECTION",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let options = ExpandOptions {
+            selection: Some("let x = 1;".to_string()),
+            ..Default::default()
+        };
+        let expanded =
+            expand_custom_command_with_options("/refactor tidy", &roots, &options).unwrap();
+        assert_eq!(expanded.prompt, "Refactor this (tidy): let x = 1;");
+        assert_eq!(expanded.warnings, Vec::new());
+
+        let expanded = expand_custom_command_in("/refactor tidy", &roots).unwrap();
+        assert_eq!(expanded.prompt, "Refactor this (tidy): ");
+        assert_eq!(expanded.warnings, vec![ExpandWarning::NoSelection]);
+
+        // Longer names that start with `$SELECTION` are not a selection.
+        write_command(cwd.path(), "mode.md", "Use $SELECTION_MODE and $SELECTIONS");
+        let expanded = expand_custom_command_in("/mode", &roots).unwrap();
+        assert_eq!(expanded.warnings, Vec::new());
+    }
+
+    #[test]
+    fn empty_prompts_warn_and_fail_in_strict_mode() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "empty.md", "");
+        let frontmatter_only = write_command(
+            cwd.path(),
+            "frontmatter_only.md",
+            "---\ndescription: Nothing\n---\n\n",
+        );
+        write_command(cwd.path(), "runs_empty.md", "@run empty\nStill here");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        for input in ["/empty", "/frontmatter_only"] {
+            let expanded = expand_custom_command_in(input, &roots).unwrap();
+            assert_eq!(
+                expanded.warnings,
+                vec![ExpandWarning::EmptyPrompt],
+                "{input}"
+            );
+        }
+        let expanded = expand_custom_command_in("/runs_empty", &roots).unwrap();
+        assert_eq!(expanded.warnings, Vec::new());
+
+        let options = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err =
+            expand_custom_command_with_options("/frontmatter_only", &roots, &options).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::EmptyPrompt(path) if *path == frontmatter_only),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn next_directive_is_exposed_and_stripped_from_prompt() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "plan.md",
+            "---\nnext: project:implement\n---\nPlan $ARGUMENTS",
+        );
+        write_command(cwd.path(), "implement.md", "Implement the plan.");
+
+        let expanded =
+            expand_custom_command_with_home("/project:plan auth", cwd.path(), None).unwrap();
+        assert_eq!(
+            expanded,
+            ExpandedCommand {
+                prompt: "Plan auth".to_string(),
+                scope: Scope::Project,
+                next: Some("project:implement".to_string()),
+                warnings: Vec::new(),
+                args_prompt: None,
+                template: None,
+            }
+        );
+    }
+
+    #[test]
+    fn args_prompt_is_surfaced_only_without_arguments() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "fix.md",
+            "---\nargs_prompt: What should I fix?\n---\nFix $ARGUMENTS",
+        );
+        write_command(
+            cwd.path(),
+            "lint.md",
+            "---\nargs_prompt: What should I lint?\n---\nLint everything",
+        );
+        let expand = |input| expand_custom_command_with_home(input, cwd.path(), None).unwrap();
+
+        let expanded = expand("/fix");
+        assert_eq!(expanded.prompt, "Fix ");
+        assert_eq!(expanded.args_prompt.as_deref(), Some("What should I fix?"));
+        assert_eq!(expand("/fix the tests").args_prompt, None);
+        // Without `$ARGUMENTS` there is nothing to ask for.
+        assert_eq!(expand("/lint").args_prompt, None);
+    }
+
+    #[test]
+    fn bare_next_defaults_to_project_scope_and_warns_when_missing() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "plan.md", "---\nnext: /review\n---\nPlan");
+
+        let expanded = expand_custom_command_with_home("/plan", cwd.path(), None).unwrap();
+        assert_eq!(expanded.next.as_deref(), Some("project:review"));
+        assert_eq!(
+            expanded.warnings,
+            vec![ExpandWarning::NextCommandNotFound(
+                "project:review".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn frontmatter_template_replaces_body() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "review.md",
+            "---\ntemplate: Review $1 in $ARGUMENTS\n---\nNotes: keep $1 first.\n",
+        );
+        write_command(
+            cwd.path(),
+            "terse.md",
+            "+++\ntemplate = \"Answer tersely: $ARGUMENTS\"\n+++\nNot sent.\n",
+        );
+
+        assert_eq!(
+            expand_prompt("/review auth src/", &cwd).unwrap(),
+            "Review auth in auth src/"
+        );
+        assert_eq!(
+            expand_prompt("/terse why", &cwd).unwrap(),
+            "Answer tersely: why"
+        );
+    }
+
+    #[test]
+    fn env_flag_selects_variant_and_falls_back_to_base() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "deploy.md", "Deploy $ARGUMENTS");
+        write_command(cwd.path(), "deploy.prod.md", "Carefully deploy $ARGUMENTS");
+        write_command(
+            cwd.path(),
+            "ops/restart.staging.md",
+            "Restart $1 on staging",
+        );
+        write_command(cwd.path(), "ops/restart.md", "Restart $1");
+
+        for (input, prompt) in [
+            ("/deploy api", "Deploy api"),
+            ("/deploy --env=prod api", "Carefully deploy api"),
+            ("/deploy --env=qa api", "Deploy api"),
+            (
+                "/project:ops__restart --env=staging web",
+                "Restart web on staging",
+            ),
+            ("/ops__restart --env=prod web", "Restart web"),
+            // Only a leading flag selects a variant.
+            ("/deploy api --env=prod", "Deploy api --env=prod"),
+        ] {
+            assert_eq!(expand_prompt(input, &cwd).unwrap(), prompt, "{input}");
+        }
+
+        let expanded = expand(Scope::Project, "deploy", "--env=prod", cwd.path()).unwrap();
+        assert_eq!(expanded.prompt, "Carefully deploy ");
+    }
+
+    #[test]
+    fn a_project_command_extends_a_user_base() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(home.path(), "base-review.md", "Review $1 carefully.");
+        write_command(
+            cwd.path(),
+            "review.md",
+            "---\nextends: user:base-review\n---\nProject rules first.\n$BASE\nThen $ARGUMENTS.",
+        );
+        write_command(
+            cwd.path(),
+            "review_more.md",
+            "---\nextends: review\n---\nAlso check $1.",
+        );
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        assert_eq!(
+            expand_custom_command_in("/review src", &roots)
+                .unwrap()
+                .prompt,
+            "Project rules first.\nReview src carefully.\nThen src."
+        );
+        // Without `$BASE` the base comes first; bases may extend others.
+        assert_eq!(
+            expand_custom_command_in("/review_more docs", &roots)
+                .unwrap()
+                .prompt,
+            "Project rules first.\nReview docs carefully.\nThen docs.\nAlso check docs."
+        );
+        // A longer name starting with `$BASE` is not the base.
+        write_command(
+            cwd.path(),
+            "compare.md",
+            "---\nextends: user:base-review\n---\nCompare with $BASELINE and $BASE_URL.",
+        );
+        assert_eq!(
+            expand_custom_command_in("/compare api", &roots)
+                .unwrap()
+                .prompt,
+            "Review api carefully.\nCompare with $BASELINE and $BASE_URL."
+        );
+    }
+
+    #[test]
+    fn extends_cycles_are_detected() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "a.md", "---\nextends: project:b\n---\nA");
+        write_command(cwd.path(), "b.md", "---\nextends: A\n---\nB");
+        write_command(cwd.path(), "self.md", "---\nextends: self\n---\nS");
+        write_command(cwd.path(), "bad.md", "---\nextends: team:x\n---\nS");
+
+        let err = expand_prompt("/a", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::ExtendsCycle(name) if name == "project:A"),
+            "{err}"
+        );
+        assert!(matches!(
+            expand_prompt("/self", &cwd),
+            Err(ExpandError::ExtendsCycle(_))
+        ));
+        assert!(matches!(
+            expand_prompt("/bad", &cwd),
+            Err(ExpandError::InvalidExtendsCommand(_))
+        ));
+    }
+
+    #[test]
+    fn postprocess_transforms_apply_to_the_expanded_prompt_in_order() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "review.md",
+            "---\npostprocess: [dedent, squeeze-blank-lines, trim]\n---\n\n\
+             \x20   Review $1:\n\n\n\x20     - carefully\n\n",
+        );
+        write_command(
+            cwd.path(),
+            "bad.md",
+            "---\npostprocess: shout\n---\n  Bad\n",
+        );
+
+        assert_eq!(
+            expand_prompt("/review src/", &cwd).unwrap(),
+            "Review src/:\n\n  - carefully"
+        );
+        let expanded = expand_custom_command_with_home("/bad", cwd.path(), None).unwrap();
+        assert_eq!(expanded.prompt, "  Bad\n");
+        assert!(matches!(
+            &expanded.warnings[..],
+            [ExpandWarning::InvalidFrontmatter(error)] if error.message.starts_with("unknown")
+        ));
+    }
+
+    #[test]
+    fn deprecated_commands_redirect_to_their_successor() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "old.md",
+            "---\ndeprecated_redirect: project:new\ndefault_args: --ignored\n---\nOld $1",
+        );
+        write_command(cwd.path(), "new.md", "New $1 [$ARGUMENTS] $INPUT");
+
+        let expanded =
+            expand_custom_command_in("/old a b", &CommandRoots::new(cwd.path(), None)).unwrap();
+        assert_eq!(expanded.prompt, "New a [a b] /old a b");
+        assert_eq!(
+            expanded.warnings,
+            vec![ExpandWarning::Deprecated(
+                "project:old".to_string(),
+                "project:new".to_string()
+            )]
+        );
+        assert_eq!(
+            expanded.warnings[0].to_string(),
+            "`/project:old` is deprecated; use `/project:new` instead"
+        );
+    }
+
+    #[test]
+    fn an_empty_successor_of_a_redirect_is_an_empty_prompt() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "old.md", "---\ndeprecated_redirect: new\n---\n");
+        let new = write_command(cwd.path(), "new.md", "---\ndescription: Nothing\n---\n");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let expanded = expand_custom_command_in("/old", &roots).unwrap();
+        assert_eq!(
+            expanded.warnings,
+            vec![
+                ExpandWarning::Deprecated("project:old".to_string(), "project:new".to_string()),
+                ExpandWarning::EmptyPrompt,
+            ]
+        );
+        let strict = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = expand_custom_command_with_options("/old", &roots, &strict).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::EmptyPrompt(path) if *path == new),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn deprecated_redirect_cycles_are_detected() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "a.md", "---\ndeprecated_redirect: b\n---\nA");
+        write_command(cwd.path(), "b.md", "---\ndeprecated_redirect: /a\n---\nB");
+        write_command(cwd.path(), "bad.md", "---\ndeprecated_redirect: a b\n---\n");
+
+        let err = expand_prompt("/a", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::RedirectCycle(name) if name == "project:a"),
+            "{err}"
+        );
+        assert!(matches!(
+            expand_prompt("/bad", &cwd),
+            Err(ExpandError::InvalidRedirectCommand(_))
+        ));
+    }
+
+    #[test]
+    fn strict_mode_lists_every_unresolved_placeholder() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "lint.md", "Lint $1");
+        write_command(
+            cwd.path(),
+            "fix.md",
+            "Fix $1 and $3 for $ARGUMENT\n@run lint $2\nThen $3 ${CODEX_TEST_UNSET_VAR}",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+        let strict = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        let err = expand_custom_command_with_options("/fix a b", &roots, &strict).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::UnresolvedPlaceholders(placeholders)
+                if placeholders == &["$3", "$ARGUMENT", "${CODEX_TEST_UNSET_VAR}"]),
+            "{err}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "unresolved placeholders: $3, $ARGUMENT, ${CODEX_TEST_UNSET_VAR}"
+        );
+        // A placeholder in a command that is `@run` is checked there.
+        let err = expand_custom_command_with_options("/lint", &roots, &strict).unwrap_err();
+        assert!(
+            matches!(err, ExpandError::UnresolvedPlaceholders(_)),
+            "{err}"
+        );
+
+        let expanded = expand_custom_command_with_options("/lint src", &roots, &strict).unwrap();
+        assert_eq!(expanded.prompt, "Lint src");
+        // Without `strict` the same command expands with blanks.
+        assert_eq!(
+            expand_prompt("/fix a b", &cwd).unwrap(),
+            "Fix a and  for $ARGUMENT\nLint b\nThen  "
+        );
+    }
+
+    #[test]
+    fn named_arguments_are_validated_against_the_schema() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "release.md",
+            "---\nargs: [{name: channel, type: enum, values: [beta, stable]}]\n---\n[$ARGUMENTS]",
+        );
+        write_command(
+            cwd.path(),
+            "retry.md",
+            "+++\nargs = [{ name = \"times\", type = \"integer\" }]\n+++\nRetry $ARGUMENTS",
+        );
+
+        assert_eq!(
+            expand_prompt("/release --channel=beta v2", &cwd).unwrap(),
+            "[--channel=beta v2]"
+        );
+        assert_eq!(expand_prompt("/release v2", &cwd).unwrap(), "[v2]");
+        assert_eq!(
+            expand_prompt("/retry --times=3", &cwd).unwrap(),
+            "Retry --times=3"
+        );
+
+        let err = expand_prompt("/release --channel=nightly", &cwd).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value `nightly` for argument `channel`: expected one of beta, stable"
+        );
+        assert!(matches!(
+            expand_prompt("/retry --times=often", &cwd),
+            Err(ExpandError::InvalidArgument(..))
+        ));
+    }
+
+    #[test]
+    fn default_args_are_prepended_and_overridden_per_key() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "deploy.md",
+            "---\ndefault_args: \"--env=dev --region=us --dry-run\"\n---\nDeploy [$ARGUMENTS]",
+        );
+        write_command(cwd.path(), "deploy.dev.md", "Deploy to dev [$ARGUMENTS]");
+        write_command(cwd.path(), "deploy.prod.md", "Deploy to prod [$ARGUMENTS]");
+        write_command(
+            cwd.path(),
+            "greet.md",
+            "---\ndefault_args: --loud world\n---\nGreet [$ARGUMENTS] [$1]",
+        );
+
+        for (input, prompt) in [
+            ("/deploy", "Deploy to dev [--region=us --dry-run]"),
+            ("/deploy api", "Deploy to dev [--region=us --dry-run api]"),
+            // The variant the user selected has no defaults of its own.
+            ("/deploy --env=prod", "Deploy to prod []"),
+            (
+                "/deploy --region=eu api",
+                "Deploy to dev [--dry-run --region=eu api]",
+            ),
+            ("/greet", "Greet [--loud world] [--loud]"),
+            (
+                "/greet --loud=no you",
+                "Greet [world --loud=no you] [world]",
+            ),
+        ] {
+            assert_eq!(expand_prompt(input, &cwd).unwrap(), prompt, "{input}");
+        }
+    }
+
+    #[test]
+    fn env_flag_may_follow_other_leading_options() {
+        assert_eq!(
+            split_env_flag("--verbose --env=prod  a  b"),
+            (Some("prod".to_string()), "--verbose a  b".to_string())
+        );
+        assert_eq!(
+            split_env_flag("a --env=prod"),
+            (None, "a --env=prod".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_env_is_an_error() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "deploy.md", "Deploy");
+
+        for input in ["/deploy --env=../x", "/deploy --env=", "/deploy --env=a.b"] {
+            let err = expand_prompt(input, &cwd).unwrap_err();
+            assert!(matches!(err, ExpandError::InvalidEnv(_)), "{input}: {err}");
+        }
+    }
+
+    #[test]
+    fn malformed_frontmatter_warns_and_body_still_expands() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "fix.md",
+            "---\nstrict_env: true\nnext = review\n---\nFix $ARGUMENTS",
+        );
+
+        let expanded = expand_custom_command_with_home("/fix it", cwd.path(), None).unwrap();
+        assert_eq!(expanded.prompt, "Fix it");
+        assert_eq!(expanded.next, None);
+        assert_eq!(
+            expanded.warnings,
+            vec![ExpandWarning::InvalidFrontmatter(FrontmatterError {
+                line: 3,
+                message: "expected `key: value`, found `next = review`".to_string(),
+            })]
+        );
+    }
+
+    #[test]
+    fn raw_commands_are_not_substituted() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_sample.md", "echo \"$1\"\n");
+        write_command(
+            cwd.path(),
+            "shell.md",
+            "---\nraw: true\n---\nUse $ARGUMENTS, ${HOME}:\n@include _sample.md\n$SELECTION",
+        );
+
+        let expanded = expand_custom_command_with_home("/shell x y", cwd.path(), None).unwrap();
+        assert_eq!(
+            expanded.prompt,
+            "Use $ARGUMENTS, ${HOME}:\necho \"$1\"\n$SELECTION"
+        );
+        assert_eq!(expanded.warnings, Vec::new());
+    }
+
+    #[test]
+    fn malformed_next_is_an_error() {
+        let cwd = TempDir::new().unwrap();
+        for next in [
+            "team:review",
+            "project:../escape",
+            "project:two words",
+            "user:",
+        ] {
+            write_command(
+                cwd.path(),
+                "plan.md",
+                &format!("---\nnext: {next}\n---\nPlan"),
+            );
+            let err = expand_prompt("/plan", &cwd).unwrap_err();
+            assert!(
+                matches!(err, ExpandError::InvalidNextCommand(_)),
+                "{next}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_cwd_is_reported_distinctly() {
+        let parent = TempDir::new().unwrap();
+        let cwd = parent.path().join("removed");
+
+        for input in ["/project:fix", "/user:fix"] {
+            let err =
+                expand_custom_command_with_home(input, &cwd, Some(parent.path())).unwrap_err();
+            assert!(
+                matches!(&err, ExpandError::CwdNotFound(path) if path == &cwd),
+                "{input}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn fallback_supplies_commands_without_a_file() {
+        let cwd = TempDir::new().unwrap();
+        let roots = CommandRoots::new(cwd.path(), None);
+        let calls = std::cell::RefCell::new(Vec::new());
+        let fallback = |scope: Scope, name: &str| {
+            calls.borrow_mut().push((scope, name.to_string()));
+            name.starts_with("gen")
+                .then(|| format!("---\ndefault_args: --dry-run\n---\n{name} with $ARGUMENTS\n"))
+        };
+        let expand = |input: &str| {
+            expand_custom_command_with_fallback(input, &roots, &ExpandOptions::default(), fallback)
+        };
+
+        // No commands directory at all.
+        assert_eq!(
+            expand("/generated x").unwrap().prompt,
+            "generated with --dry-run x\n"
+        );
+        let err = expand("/missing").unwrap_err();
+        assert!(matches!(err, ExpandError::NoCommandsDir(_)), "{err}");
+
+        // A file always wins, and the fallback only runs when lookup fails.
+        write_command(cwd.path(), "gen_file.md", "from disk");
+        calls.borrow_mut().clear();
+        assert_eq!(expand("/gen_file").unwrap().prompt, "from disk");
+        assert!(calls.borrow().is_empty());
+        assert_eq!(
+            expand("/generated --env=prod").unwrap().prompt,
+            "generated with --dry-run\n"
+        );
+        assert_eq!(
+            calls.borrow().as_slice(),
+            [(Scope::Project, "generated".to_string())]
+        );
+        let err = expand("/missing").unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+
+        let include = |_: Scope, _: &str| Some("@include _shared.md\n".to_string());
+        let err = expand_custom_command_with_fallback(
+            "/included",
+            &roots,
+            &ExpandOptions::default(),
+            include,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ExpandError::IncludeNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn missing_commands_dir_is_reported_distinctly() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let dotfiles = TempDir::new().unwrap();
+
+        let project_dir = Scope::Project.commands_dir(cwd.path());
+        let err = expand_prompt("/project:fix", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::NoCommandsDir(dir) if dir == &project_dir),
+            "{err}"
+        );
+
+        // One existing root among several is enough for a plain miss.
+        let roots = CommandRoots::new(cwd.path(), Some(home.path())).with_user_dirs(vec![
+            home.path().join("missing"),
+            dotfiles.path().to_path_buf(),
+        ]);
+        let err = expand_custom_command_in("/user:fix", &roots).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::FileNotFound(path) if path.starts_with(home.path())),
+            "{err}"
+        );
+
+        write_command(cwd.path(), "lint.md", "Lint");
+        let err = expand_prompt("/project:fix", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn commands_config_sets_strict_env_for_the_tree() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "config.toml", "strict_env = true\n");
+        write_command(
+            cwd.path(),
+            "deploy.md",
+            "Deploy to ${CODEX_TEST_UNSET_TARGET}",
+        );
+        write_command(
+            cwd.path(),
+            "lenient.md",
+            "---\nstrict_env: false\n---\n[${CODEX_TEST_UNSET_TARGET}]",
+        );
+        write_command(cwd.path(), "path.md", "PATH is ${PATH}");
+
+        let err = expand_prompt("/project:deploy", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::UndefinedEnvVar(name) if name == "CODEX_TEST_UNSET_TARGET"),
+            "{err}"
+        );
+        // Frontmatter overrides the directory-wide setting.
+        assert_eq!(expand_prompt("/project:lenient", &cwd).unwrap(), "[]");
+        // Set variables expand as usual under strict_env.
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(
+            expand_prompt("/project:path", &cwd).unwrap(),
+            format!("PATH is {path}")
+        );
+
+        // The config file is not a command.
+        assert_eq!(
+            crate::commands::discover_custom_commands_with_home(cwd.path(), None),
+            vec![
+                "project:deploy".to_string(),
+                "project:lenient".to_string(),
+                "project:path".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_config_sets_size_cap() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "config.toml", "max_file_bytes = 8\n");
+        write_command(cwd.path(), "short.md", "@include _long.md");
+        write_command(cwd.path(), "_long.md", "longer than eight bytes");
+
+        let err = expand_prompt("/project:short", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::FileTooLarge(_, 8)), "{err}");
+    }
+
+    #[test]
+    fn total_expansion_is_capped_across_includes_and_runs() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "config.toml", "max_expansion_bytes = 100\n");
+        // Each level doubles the text, so `_4` expands to 80 bytes and `_5` to
+        // 160.
+        write_command(cwd.path(), "_0.md", "abcd");
+        for level in 1..=5 {
+            let partial = format!("@include _{}.md\n", level - 1);
+            write_command(cwd.path(), &format!("_{level}.md"), &partial.repeat(2));
+        }
+        write_command(cwd.path(), "small.md", "@include _4.md");
+        write_command(cwd.path(), "big.md", "@include _5.md");
+        write_command(cwd.path(), "runs.md", "@run small\n@run small\n");
+
+        assert_eq!(expand_prompt("/small", &cwd).unwrap().len(), 80);
+        let err = expand_prompt("/big", &cwd).unwrap_err();
+        assert!(
+            matches!(err, ExpandError::ExpansionTooLarge(_, 100)),
+            "{err}"
+        );
+        let err = expand_prompt("/runs", &cwd).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::ExpansionTooLarge(path, 100) if path.ends_with("runs.md")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unknown_scope_is_an_error() {
+        let cwd = TempDir::new().unwrap();
+        let err = expand_prompt("/team:fix", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "team"));
+    }
+
+    #[test]
+    fn empty_scope_is_its_own_error() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix");
+        let err = expand_prompt("/:fix", &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::EmptyScope), "{err}");
+        assert_eq!(
+            err.to_string(),
+            "missing command scope before `:`: use `project:` or `user:`"
+        );
+    }
+
+    #[test]
+    fn names_that_escape_the_root_are_rejected() {
+        let cwd = TempDir::new().unwrap();
+        for input in ["/project:..__secret", "/project:a/b", "/project:"] {
+            let err = expand_prompt(input, &cwd).unwrap_err();
+            assert!(matches!(err, ExpandError::InvalidName(_)), "{input}: {err}");
+        }
+    }
+
+    #[test]
+    fn over_long_names_are_rejected_before_touching_the_disk() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix");
+        let longest = "a".repeat(252);
+        write_command(cwd.path(), &format!("{longest}.md"), "Long");
+
+        assert_eq!(expand_prompt(&format!("/{longest}"), &cwd).unwrap(), "Long");
+        for name in [
+            "a".repeat(253),
+            "a".repeat(5000),
+            format!("{}__fix", "d".repeat(256)),
+        ] {
+            let err = expand_prompt(&format!("/{name}"), &cwd).unwrap_err();
+            assert!(matches!(err, ExpandError::NameTooLong(_)), "{err}");
+        }
+        let dir = "d".repeat(255);
+        let err = expand_prompt(&format!("/{dir}__fix"), &cwd).unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+}
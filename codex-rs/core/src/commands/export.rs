@@ -0,0 +1,120 @@
+//! Exporting every command as one Markdown document, e.g. for documentation.
+
+use std::path::Path;
+
+use super::CommandRoots;
+use super::ExpandError;
+use super::discover::discover_commands;
+use super::discover::summary;
+use super::find_command;
+use super::frontmatter::split_frontmatter;
+
+/// Export every command available from `project_root`, with `home` for the
+/// user scope (`None` disables it), as a Markdown document: a heading with
+/// each command's `/scope:name`, its description (the first line of its
+/// body, as in [`DiscoveredCommand::load_summary`]) and its body in a code
+/// block. Commands appear in discovery order, which is sorted by name within
+/// each scope, so the same commands always produce the same document.
+///
+/// [`DiscoveredCommand::load_summary`]: super::DiscoveredCommand::load_summary
+pub fn export_all(project_root: &Path, home: Option<&Path>) -> String {
+    export_all_in(&CommandRoots::new(project_root, home))
+}
+
+/// Like [`export_all`], but exporting the commands in `roots`.
+///
+/// Bodies are not expanded: includes and placeholders are left as written
+/// and only the frontmatter is dropped. A command that cannot be read, e.g.
+/// because it is larger than its directory's `max_file_bytes`, is listed
+/// with the reason instead of its body.
+pub fn export_all_in(roots: &CommandRoots) -> String {
+    let mut out = String::from("# Custom commands\n");
+    for command in discover_commands(roots) {
+        out.push_str(&format!("\n## /{}\n\n", command.full_name()));
+        match find_command(roots, command.scope, &command.name) {
+            Ok(found) => {
+                let body = split_frontmatter(&found.contents).1;
+                if let Some(description) = summary(body) {
+                    out.push_str(&description);
+                    out.push_str("\n\n");
+                }
+                push_code_block(&mut out, body);
+            }
+            Err(e) => out.push_str(&format!("Not exported: {}\n", skip_reason(&e))),
+        }
+    }
+    out
+}
+
+fn skip_reason(e: &ExpandError) -> String {
+    match e {
+        ExpandError::FileTooLarge(_, max_bytes) => format!("larger than {max_bytes} bytes"),
+        e => e.to_string(),
+    }
+}
+
+/// Append `body` fenced with more backticks than any run inside it.
+fn push_code_block(out: &mut String, body: &str) {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    out.push_str(&fence);
+    out.push_str("markdown\n");
+    out.push_str(body);
+    if !body.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&fence);
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn export_has_a_section_per_command() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "review/security.md",
+            "---\nnext: project:fix\n---\n# Security review\n\nAudit $ARGUMENTS.\n",
+        );
+        write_command(cwd.path(), "fix.md", "Fix it:\n```\ncode\n```");
+        write_command(cwd.path(), "_partial.md", "partial");
+        write_command(home.path(), "greet.md", "");
+
+        let export = export_all(cwd.path(), Some(home.path()));
+        assert_eq!(
+            export,
+            "# Custom commands\n\
+             \n## /project:fix\n\nFix it:\n\n\
+             ````markdown\nFix it:\n```\ncode\n```\n````\n\
+             \n## /project:review__security\n\nSecurity review\n\n\
+             ```markdown\n# Security review\n\nAudit $ARGUMENTS.\n```\n\
+             \n## /user:greet\n\n```markdown\n\n```\n"
+        );
+        assert_eq!(export, export_all(cwd.path(), Some(home.path())));
+    }
+
+    #[test]
+    fn commands_over_the_size_cap_are_not_exported() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "config.toml", "max_file_bytes = 8\n");
+        write_command(cwd.path(), "big.md", "longer than eight bytes");
+        write_command(cwd.path(), "ok.md", "Short");
+
+        let export = export_all(cwd.path(), None);
+        assert!(
+            export.contains("## /project:big\n\nNot exported: larger than 8 bytes\n"),
+            "{export}"
+        );
+        assert!(!export.contains("longer than eight"), "{export}");
+        assert!(export.contains("```markdown\nShort\n```\n"), "{export}");
+    }
+}
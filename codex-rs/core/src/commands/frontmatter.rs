@@ -0,0 +1,534 @@
+//! Optional metadata block at the top of a command file.
+//!
+//! A command file may start with a block delimited by `---` lines containing
+//! simple `key: value` pairs:
+//!
+//! ```text
+//! ---
+//! next: project:followup
+//! strict_env: true
+//! ---
+//! Prompt body…
+//! ```
+//!
+//! Values may be wrapped in single or double quotes. Unknown keys, blank
+//! lines and `#` comments are ignored. The `args` key is the exception to
+//! single values: it takes a schema in flow syntax (see [`ArgSpec`]). The
+//! list keys `visibility` and `postprocess` take a flow sequence such as
+//! `[dedent, trim]` or a single value.
+//!
+//! When a `template` key is present its value is the prompt template and the
+//! body is treated as author notes, which are never sent to the model:
+//!
+//! ```text
+//! ---
+//! template: Review $ARGUMENTS for security issues.
+//! ---
+//! Notes for maintainers of this command…
+//! ```
+//!
+//! The same metadata can instead be written as TOML between `+++` lines:
+//!
+//! ```text
+//! +++
+//! next = "project:followup"
+//! strict_env = true
+//! +++
+//! ```
+//!
+//! Unknown keys are ignored here too; a block that is not valid TOML yields
+//! no metadata. Only a block at the very top of the file is recognized, and
+//! it is removed from the prompt.
+//!
+//! Malformed lines (and an invalid TOML block) do not stop expansion; they
+//! are reported as [`FrontmatterError`]s so authors can fix them.
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::postprocess::PostProcess;
+use super::postprocess::parse_postprocess;
+use super::schema::ArgSpec;
+use super::schema::ArgSpecToml;
+use super::schema::parse_yaml_schema;
+use super::schema::schema_from_toml;
+
+const YAML_DELIMITER: &str = "---";
+const TOML_DELIMITER: &str = "+++";
+
+/// At most this much of a command file is read by [`read_frontmatter_flags`];
+/// a block that does not close within it is treated as unterminated.
+const MAX_FRONTMATTER_BYTES: u64 = 16 * 1024;
+
+/// Metadata parsed from a command's frontmatter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frontmatter {
+    /// Command the caller may offer to run once the model has responded,
+    /// e.g. `project:followup`.
+    pub next: Option<String>,
+    /// Overrides `strict_env` from the commands directory's `config.toml`.
+    /// Only `true` and `false` are recognized.
+    pub strict_env: Option<bool>,
+    /// Prompt template used instead of the body.
+    pub template: Option<String>,
+    /// Arguments prepended to the ones the command is invoked with, e.g.
+    /// `--env=dev`. An option the user passes overrides the default with the
+    /// same key.
+    pub default_args: Option<String>,
+    /// When `true`, no placeholders are substituted: the prompt is the body
+    /// (or template) verbatim, apart from `@include` directives.
+    pub raw: Option<bool>,
+    /// Question for the caller to ask the user when the command uses
+    /// `$ARGUMENTS` but is invoked without arguments, e.g. `What should I
+    /// fix?`.
+    pub args_prompt: Option<String>,
+    /// When `true`, discovery (and so completion and search) does not list
+    /// the command, but it can still be invoked by name or `@run`, e.g. a
+    /// building block of other commands. Only `true` and `false` are
+    /// recognized.
+    pub hidden: Option<bool>,
+    /// Command this one inherits from, e.g. `user:base-review`. The base is
+    /// expanded with the same arguments and its prompt replaces `$BASE` in
+    /// this command's, or comes before it when there is no `$BASE`.
+    pub extends: Option<String>,
+    /// Named arguments the command accepts, checked when it is expanded.
+    /// See [`ArgSpec`].
+    pub args: Option<Vec<ArgSpec>>,
+    /// Position of the command in curated menus, e.g. `10`. Commands with an
+    /// order come first, lowest first; see
+    /// [`by_display_order`](super::by_display_order).
+    pub order: Option<i32>,
+    /// Projects the command is listed in, e.g. `[codex, ~/work/*]`; see
+    /// [module docs](super). Discovery elsewhere skips the command, but it
+    /// can still be invoked by name.
+    pub visibility: Option<Vec<String>>,
+    /// Successor of a renamed command, e.g. `project:newname`. Invoking this
+    /// command expands the successor instead, with the same arguments, with
+    /// a warning; the rest of this command's file is ignored.
+    pub deprecated_redirect: Option<String>,
+    /// Transforms applied to the expanded prompt, in order, e.g.
+    /// `[dedent, squeeze-blank-lines]`. See [`PostProcess`].
+    pub postprocess: Option<Vec<PostProcess>>,
+}
+
+/// A problem in a frontmatter block. The metadata it affects is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontmatterError {
+    /// 1-based line in the command file; the opening delimiter is line 1.
+    pub line: usize,
+    pub message: String,
+}
+
+/// `+++` frontmatter, deserialized with the same keys as [`Frontmatter`].
+#[derive(Deserialize, Debug, Default)]
+struct FrontmatterToml {
+    next: Option<String>,
+    strict_env: Option<bool>,
+    template: Option<String>,
+    default_args: Option<String>,
+    raw: Option<bool>,
+    args_prompt: Option<String>,
+    hidden: Option<bool>,
+    extends: Option<String>,
+    args: Option<Vec<ArgSpecToml>>,
+    order: Option<i32>,
+    visibility: Option<Vec<String>>,
+    deprecated_redirect: Option<String>,
+    postprocess: Option<Vec<String>>,
+}
+
+/// Split `contents` into its frontmatter and the remaining body. Files
+/// without a (terminated) frontmatter block are returned unchanged.
+pub(super) fn split_frontmatter(contents: &str) -> (Frontmatter, &str) {
+    let (frontmatter, body, _) = split_frontmatter_checked(contents);
+    (frontmatter, body)
+}
+
+/// Like [`split_frontmatter`], also returning the problems found in the
+/// block.
+pub(super) fn split_frontmatter_checked(
+    contents: &str,
+) -> (Frontmatter, &str, Vec<FrontmatterError>) {
+    let mut errors = Vec::new();
+    if let Some((block, body)) = frontmatter_block(contents, YAML_DELIMITER) {
+        (parse_yaml(block, &mut errors), body, errors)
+    } else if let Some((block, body)) = frontmatter_block(contents, TOML_DELIMITER) {
+        (parse_toml(block, &mut errors), body, errors)
+    } else {
+        (Frontmatter::default(), contents, errors)
+    }
+}
+
+/// What discovery reports about the frontmatter of a command file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct FrontmatterFlags {
+    /// The file starts with a (terminated) frontmatter block.
+    pub(super) present: bool,
+    /// The frontmatter has `hidden: true`.
+    pub(super) hidden: bool,
+    /// The frontmatter `order`.
+    pub(super) order: Option<i32>,
+    /// The frontmatter `visibility`.
+    pub(super) visibility: Option<Vec<String>>,
+}
+
+/// The [`FrontmatterFlags`] of the command file `contents`.
+pub(super) fn frontmatter_flags(contents: &str) -> FrontmatterFlags {
+    let (frontmatter, body) = split_frontmatter(contents);
+    FrontmatterFlags {
+        present: body.len() < contents.len(),
+        hidden: frontmatter.hidden == Some(true),
+        order: frontmatter.order,
+        visibility: frontmatter.visibility,
+    }
+}
+
+/// The [`FrontmatterFlags`] of the command file at `path`, reading only its
+/// frontmatter block: the first line when the file does not start with a
+/// delimiter, otherwise up to the closing one, so that listing commands does
+/// not read their bodies. An unreadable file has no flags.
+pub(super) fn read_frontmatter_flags(path: &Path) -> FrontmatterFlags {
+    read_frontmatter_prefix(path)
+        .map(|prefix| frontmatter_flags(&prefix))
+        .unwrap_or_default()
+}
+
+fn read_frontmatter_prefix(path: &Path) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut reader = BufReader::new(file.take(MAX_FRONTMATTER_BYTES));
+    let mut prefix = String::new();
+    reader.read_line(&mut prefix).ok()?;
+    if let Some(rest) = prefix.strip_prefix('\u{feff}') {
+        prefix = rest.to_string();
+    }
+    let delimiter = match prefix.trim_end() {
+        YAML_DELIMITER => YAML_DELIMITER,
+        TOML_DELIMITER => TOML_DELIMITER,
+        _ => return Some(prefix),
+    };
+    loop {
+        let start = prefix.len();
+        if reader.read_line(&mut prefix).ok()? == 0 || prefix[start..].trim_end() == delimiter {
+            return Some(prefix);
+        }
+    }
+}
+
+/// Line of the file on which `offset` into the block falls.
+fn line_of(block: &str, offset: usize) -> usize {
+    block[..offset.min(block.len())].matches('\n').count() + 2
+}
+
+fn parse_toml(block: &str, errors: &mut Vec<FrontmatterError>) -> Frontmatter {
+    let toml = match toml::from_str::<FrontmatterToml>(block) {
+        Ok(toml) => toml,
+        Err(e) => {
+            errors.push(FrontmatterError {
+                line: line_of(block, e.span().map_or(0, |span| span.start)),
+                message: e.message().trim().to_string(),
+            });
+            FrontmatterToml::default()
+        }
+    };
+    let args = toml.args.and_then(|args| {
+        schema_from_toml(args)
+            .map_err(|message| {
+                errors.push(FrontmatterError {
+                    line: line_of(block, block.find("args").unwrap_or(0)),
+                    message,
+                });
+            })
+            .ok()
+    });
+    let postprocess = toml.postprocess.and_then(|names| {
+        parse_postprocess(&names)
+            .map_err(|message| {
+                errors.push(FrontmatterError {
+                    line: line_of(block, block.find("postprocess").unwrap_or(0)),
+                    message,
+                });
+            })
+            .ok()
+    });
+    Frontmatter {
+        next: toml.next,
+        strict_env: toml.strict_env,
+        template: toml.template,
+        default_args: toml.default_args,
+        raw: toml.raw,
+        args_prompt: toml.args_prompt,
+        hidden: toml.hidden,
+        extends: toml.extends,
+        args,
+        order: toml.order,
+        visibility: toml.visibility,
+        deprecated_redirect: toml.deprecated_redirect,
+        postprocess,
+    }
+}
+
+fn parse_yaml(block: &str, errors: &mut Vec<FrontmatterError>) -> Frontmatter {
+    let mut frontmatter = Frontmatter::default();
+    for (index, line) in block.lines().enumerate() {
+        let line_number = index + 2;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            errors.push(FrontmatterError {
+                line: line_number,
+                message: format!("expected `key: value`, found `{line}`"),
+            });
+            continue;
+        };
+        let value = unquote(value.trim()).to_string();
+        match key.trim() {
+            "next" => frontmatter.next = Some(value),
+            key @ ("strict_env" | "raw" | "hidden") => {
+                let parsed = value.parse().ok();
+                if parsed.is_none() {
+                    errors.push(FrontmatterError {
+                        line: line_number,
+                        message: format!("`{key}` must be `true` or `false`, found `{value}`"),
+                    });
+                }
+                match key {
+                    "raw" => frontmatter.raw = parsed,
+                    "hidden" => frontmatter.hidden = parsed,
+                    _ => frontmatter.strict_env = parsed,
+                }
+            }
+            "template" => frontmatter.template = Some(value),
+            "default_args" => frontmatter.default_args = Some(value),
+            "args_prompt" => frontmatter.args_prompt = Some(value),
+            "extends" => frontmatter.extends = Some(value),
+            "deprecated_redirect" => frontmatter.deprecated_redirect = Some(value),
+            "order" => match value.parse() {
+                Ok(order) => frontmatter.order = Some(order),
+                Err(_) => errors.push(FrontmatterError {
+                    line: line_number,
+                    message: format!("`order` must be an integer, found `{value}`"),
+                }),
+            },
+            "visibility" => frontmatter.visibility = Some(parse_yaml_list(&value)),
+            "postprocess" => match parse_postprocess(&parse_yaml_list(&value)) {
+                Ok(postprocess) => frontmatter.postprocess = Some(postprocess),
+                Err(message) => errors.push(FrontmatterError {
+                    line: line_number,
+                    message,
+                }),
+            },
+            "args" => match parse_yaml_schema(&value) {
+                Ok(schema) => frontmatter.args = Some(schema),
+                Err(message) => errors.push(FrontmatterError {
+                    line: line_number,
+                    message,
+                }),
+            },
+            _ => {}
+        }
+    }
+    frontmatter
+}
+
+/// Return the text between the opening and closing `delimiter` lines, and
+/// the body that follows the closing one.
+fn frontmatter_block<'a>(contents: &'a str, delimiter: &str) -> Option<(&'a str, &'a str)> {
+    let rest = contents.strip_prefix(delimiter)?;
+    let rest = rest
+        .strip_prefix('\n')
+        .or_else(|| rest.strip_prefix("\r\n"))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// A YAML flow sequence of plain or quoted strings, e.g. `[a, "b"]`, or a
+/// single string as a one-item list.
+fn parse_yaml_list(value: &str) -> Vec<String> {
+    let items = value
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(value);
+    items
+        .split(',')
+        .map(|item| unquote(item.trim()))
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_next_and_strips_block() {
+        let (frontmatter, body) =
+            split_frontmatter("---\n# chain\nnext: \"project:followup\"\n---\nDo the thing.\n");
+        assert_eq!(frontmatter.next.as_deref(), Some("project:followup"));
+        assert_eq!(body, "Do the thing.\n");
+    }
+
+    #[test]
+    fn parses_strict_env_booleans_only() {
+        let (frontmatter, _) = split_frontmatter("---\nstrict_env: false\n---\n");
+        assert_eq!(frontmatter.strict_env, Some(false));
+
+        let (frontmatter, _) = split_frontmatter("---\nstrict_env: yes\n---\n");
+        assert_eq!(frontmatter.strict_env, None);
+    }
+
+    #[test]
+    fn yaml_and_toml_blocks_yield_the_same_metadata() {
+        let (yaml, yaml_body) =
+            split_frontmatter("---\nnext: project:followup\nstrict_env: true\n---\nBody\n");
+        let (toml, toml_body) =
+            split_frontmatter("+++\nnext = \"project:followup\"\nstrict_env = true\n+++\nBody\n");
+
+        assert_eq!(
+            yaml,
+            Frontmatter {
+                next: Some("project:followup".to_string()),
+                strict_env: Some(true),
+                template: None,
+                default_args: None,
+                raw: None,
+                args_prompt: None,
+                hidden: None,
+                extends: None,
+                args: None,
+                order: None,
+                visibility: None,
+                deprecated_redirect: None,
+                postprocess: None,
+            }
+        );
+        assert_eq!(toml, yaml);
+        assert_eq!(toml_body, "Body\n");
+        assert_eq!(yaml_body, toml_body);
+    }
+
+    #[test]
+    fn parses_args_prompt_in_both_formats() {
+        let (yaml, _) = split_frontmatter("---\nargs_prompt: \"What should I fix?\"\n---\nFix");
+        let (toml, _) = split_frontmatter("+++\nargs_prompt = \"What should I fix?\"\n+++\nFix");
+        assert_eq!(yaml.args_prompt.as_deref(), Some("What should I fix?"));
+        assert_eq!(toml, yaml);
+    }
+
+    #[test]
+    fn parses_integer_order_in_both_formats() {
+        let (yaml, _, errors) = split_frontmatter_checked("---\norder: -5\n---\nFix");
+        let (toml, _) = split_frontmatter("+++\norder = -5\n+++\nFix");
+        assert_eq!(yaml.order, Some(-5));
+        assert_eq!(errors, Vec::new());
+        assert_eq!(toml, yaml);
+
+        let (yaml, _, errors) = split_frontmatter_checked("---\norder: first\n---\nFix");
+        assert_eq!(yaml.order, None);
+        assert_eq!(
+            errors[0].message,
+            "`order` must be an integer, found `first`"
+        );
+    }
+
+    #[test]
+    fn parses_template_in_both_formats() {
+        let (yaml, body) = split_frontmatter("---\ntemplate: 'Fix $1'\n---\nNotes\n");
+        assert_eq!(yaml.template.as_deref(), Some("Fix $1"));
+        assert_eq!(body, "Notes\n");
+
+        let (toml, _) = split_frontmatter("+++\ntemplate = \"Fix $1\"\n+++\nNotes\n");
+        assert_eq!(toml, yaml);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported_and_skipped() {
+        let contents =
+            "---\nnext: project:followup\n\nthis is not yaml\nstrict_env: maybe\n---\nBody\n";
+        let (frontmatter, body, errors) = split_frontmatter_checked(contents);
+
+        assert_eq!(frontmatter.next.as_deref(), Some("project:followup"));
+        assert_eq!(frontmatter.strict_env, None);
+        assert_eq!(body, "Body\n");
+        assert_eq!(
+            errors,
+            vec![
+                FrontmatterError {
+                    line: 4,
+                    message: "expected `key: value`, found `this is not yaml`".to_string(),
+                },
+                FrontmatterError {
+                    line: 5,
+                    message: "`strict_env` must be `true` or `false`, found `maybe`".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_toml_is_reported() {
+        let (frontmatter, body, errors) =
+            split_frontmatter_checked("+++\nnext = \"unterminated\n+++\nBody\n");
+        assert_eq!(frontmatter, Frontmatter::default());
+        assert_eq!(body, "Body\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+    }
+
+    #[test]
+    fn only_a_leading_block_is_recognized() {
+        let contents = "Intro\n+++\nnext = \"project:followup\"\n+++\n";
+        let (frontmatter, body) = split_frontmatter(contents);
+        assert_eq!(frontmatter, Frontmatter::default());
+        assert_eq!(body, contents);
+
+        // A TOML block followed by a YAML-looking one: only the first counts.
+        let (frontmatter, body) =
+            split_frontmatter("+++\nstrict_env = false\n+++\n---\nnext: x\n---\n");
+        assert_eq!(frontmatter.strict_env, Some(false));
+        assert_eq!(frontmatter.next, None);
+        assert_eq!(body, "---\nnext: x\n---\n");
+    }
+
+    #[test]
+    fn file_without_frontmatter_is_unchanged() {
+        let contents = "Just a prompt.\n---\nnext: nope\n---\n";
+        let (frontmatter, body) = split_frontmatter(contents);
+        assert_eq!(frontmatter, Frontmatter::default());
+        assert_eq!(body, contents);
+    }
+
+    #[test]
+    fn unterminated_block_is_not_frontmatter() {
+        let contents = "---\nnext: project:followup\nbody";
+        let (frontmatter, body) = split_frontmatter(contents);
+        assert_eq!(frontmatter.next, None);
+        assert_eq!(body, contents);
+    }
+}
@@ -0,0 +1,232 @@
+//! Expanding every command that matches a glob, for batch runs.
+
+use std::path::Component;
+use std::path::Path;
+
+use super::CommandRoots;
+use super::DiscoveredCommand;
+use super::ExpandError;
+use super::Scope;
+use super::command_file_stem;
+use super::command_key;
+use super::discover::discover_commands;
+use super::expand::expand_in;
+
+/// Expand every command matching `pattern`, e.g. `project:review/* src/`,
+/// with the arguments after the pattern. Returns `(scope:name, prompt)`
+/// pairs in discovery order. The user scope is resolved against `$HOME` and
+/// `CODEX_COMMANDS_PATH`.
+///
+/// The pattern is matched against the command file's path relative to its
+/// commands directory, without the `.md` extension, so directories are
+/// written with `/` rather than `__`. `*` and `?` match within one path
+/// segment and never cross a `/`; a `**` segment matches any number of
+/// directories, including none. Matching is case-insensitive, like command
+/// names. Without a scope prefix the project scope is used. No match is not
+/// an error; the first command that fails to expand is.
+pub fn expand_glob(pattern: &str, cwd: &Path) -> Result<Vec<(String, String)>, ExpandError> {
+    expand_glob_in(pattern, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`expand_glob`], but matching the commands in `roots`.
+pub fn expand_glob_in(
+    pattern: &str,
+    roots: &CommandRoots,
+) -> Result<Vec<(String, String)>, ExpandError> {
+    let pattern = pattern.trim_start();
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    let (pattern, args) = match pattern.split_once(char::is_whitespace) {
+        Some((pattern, args)) => (pattern, args.trim_start()),
+        None => (pattern, ""),
+    };
+    let (scope, pattern) = match pattern.split_once(':') {
+        Some(("", _)) => return Err(ExpandError::EmptyScope),
+        Some((prefix, pattern)) => (
+            Scope::from_prefix(prefix)
+                .ok_or_else(|| ExpandError::UnknownScope(prefix.to_string()))?,
+            pattern,
+        ),
+        None => (Scope::Project, pattern),
+    };
+    if pattern.is_empty() {
+        return Err(ExpandError::InvalidName(pattern.to_string()));
+    }
+    let pattern: Vec<String> = pattern.split('/').map(command_key).collect();
+
+    let mut expanded = Vec::new();
+    for command in discover_commands(roots) {
+        if command.scope != scope {
+            continue;
+        }
+        let Some(segments) = path_segments(&command.relative_path) else {
+            continue;
+        };
+        if glob_matches(&pattern, &segments) {
+            let prompt = expand_in(scope, &command.name, args, roots)?.prompt;
+            expanded.push((command.full_name(), prompt));
+        }
+    }
+    Ok(expanded)
+}
+
+/// The case-folded segments of a command file's relative path, with the
+/// extension removed from the last one.
+fn path_segments(relative: &Path) -> Option<Vec<String>> {
+    let mut segments = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(segment) => segment.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<&str>>>()?;
+    let file_name = segments.pop()?;
+    segments.push(command_file_stem(file_name)?);
+    Some(segments.into_iter().map(command_key).collect())
+}
+
+/// Whether the project whose root is `project_root` is one of those the
+/// `visibility` frontmatter entries name (see the [module docs](super)).
+pub(super) fn visible_in_project(visibility: &[String], project_root: &Path) -> bool {
+    fn segments(path: &str) -> Vec<String> {
+        path.trim_end_matches('/')
+            .split('/')
+            .map(str::to_string)
+            .collect()
+    }
+
+    let name = project_root.file_name().and_then(|name| name.to_str());
+    let path = project_root.to_str();
+    visibility.iter().any(|entry| {
+        if entry.contains('/') {
+            path.is_some_and(|path| glob_matches(&segments(entry), &segments(path)))
+        } else {
+            name.is_some_and(|name| segment_matches(entry.as_bytes(), name.as_bytes()))
+        }
+    })
+}
+
+/// Whether a [`DiscoveryOptions`](super::DiscoveryOptions) pattern matches
+/// `command`.
+pub(super) fn name_matches(pattern: &str, command: &DiscoveredCommand) -> bool {
+    let name = if pattern.contains(':') {
+        command.full_name()
+    } else {
+        command.name.clone()
+    };
+    segment_matches(
+        command_key(pattern).as_bytes(),
+        command_key(&name).as_bytes(),
+    )
+}
+
+fn glob_matches(pattern: &[String], segments: &[String]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=segments.len()).any(|skip| glob_matches(rest, &segments[skip..]))
+        }
+        Some((first, rest)) => match segments.split_first() {
+            Some((segment, remaining)) => {
+                segment_matches(first.as_bytes(), segment.as_bytes())
+                    && glob_matches(rest, remaining)
+            }
+            None => false,
+        },
+    }
+}
+
+/// `*` and `?` wildcards within a single path segment.
+fn segment_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| segment_matches(rest, &text[skip..])),
+        Some((b'?', rest)) => match std::str::from_utf8(text)
+            .ok()
+            .and_then(|text| text.chars().next())
+        {
+            Some(c) => segment_matches(rest, &text[c.len_utf8()..]),
+            None => false,
+        },
+        Some((c, rest)) => text.first() == Some(c) && segment_matches(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn names(expanded: &[(String, String)]) -> Vec<&str> {
+        expanded.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn star_matches_files_in_one_directory_with_shared_args() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "review/security.md",
+            "Security review of $ARGUMENTS",
+        );
+        write_command(cwd.path(), "review/Style.md", "Style review of $1");
+        write_command(cwd.path(), "review/deep/perf.md", "Perf review of $1");
+        write_command(cwd.path(), "review/_shared.md", "partial");
+        write_command(cwd.path(), "fix.md", "Fix $1");
+        write_command(home.path(), "review/personal.md", "Mine");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let expanded = expand_glob_in("/project:review/* src/", &roots).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                (
+                    "project:review__Style".to_string(),
+                    "Style review of src/".to_string()
+                ),
+                (
+                    "project:review__security".to_string(),
+                    "Security review of src/".to_string()
+                ),
+            ]
+        );
+
+        let expanded = expand_glob_in("review/** src/", &roots).unwrap();
+        assert_eq!(
+            names(&expanded),
+            vec![
+                "project:review__Style",
+                "project:review__deep__perf",
+                "project:review__security",
+            ]
+        );
+
+        let expanded = expand_glob_in("user:review/*", &roots).unwrap();
+        assert_eq!(names(&expanded), vec!["user:review__personal"]);
+    }
+
+    #[test]
+    fn wildcards_stay_within_a_segment() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "revert.md", "");
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        assert_eq!(
+            names(&expand_glob_in("rev*", &roots).unwrap()),
+            vec!["project:revert"]
+        );
+        assert_eq!(
+            names(&expand_glob_in("*/s?curity", &roots).unwrap()),
+            vec!["project:review__security"]
+        );
+        assert_eq!(expand_glob_in("nothing/*", &roots).unwrap(), Vec::new());
+
+        let err = expand_glob_in("team:*", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "team"));
+    }
+}
@@ -0,0 +1,376 @@
+//! Recently invoked commands, for ordering completions most recently used
+//! first.
+//!
+//! The history is `$HOME/.codex/commands_history`: one `scope:name` per
+//! line, most recent first. A missing, unreadable or malformed file is
+//! treated as an empty history.
+//!
+//! [`completion_manifest`] lists every command with its arguments for
+//! completion scripts generated ahead of time, which have no history.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::CommandRoots;
+use super::Scope;
+use super::command_key;
+use super::discover::discover_commands;
+use super::discover::discover_scope;
+use super::discover::summary;
+use super::escape_command_name;
+use super::expand::parse_invocation;
+use super::find_command;
+use super::frontmatter::split_frontmatter;
+use super::home_dir;
+
+/// Location of the history file relative to `$HOME`.
+const HISTORY_FILE: &str = ".codex/commands_history";
+
+/// Only this many of the most recent commands are remembered.
+const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// Record that `name` (e.g. `project:fix`, or a whole `/fix args` input) was
+/// invoked, in `$HOME`'s history. Does nothing when `HOME` is not set.
+pub fn record_invocation(name: &str) -> std::io::Result<()> {
+    match home_dir() {
+        Some(home) => record_invocation_with_home(name, &home),
+        None => Ok(()),
+    }
+}
+
+/// Like [`record_invocation`], with an explicit home directory.
+pub fn record_invocation_with_home(name: &str, home: &Path) -> std::io::Result<()> {
+    let Some(name) = normalize(name) else {
+        return Ok(());
+    };
+    let key = command_key(&name);
+    let mut history = load_history(home);
+    history.retain(|entry| command_key(entry) != key);
+    history.insert(0, name);
+    history.truncate(MAX_HISTORY_ENTRIES);
+
+    let path = history_path(home);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut contents = history.join("\n");
+    contents.push('\n');
+    std::fs::write(path, contents)
+}
+
+/// The recorded commands in `home`'s history as `scope:name`, most recent
+/// first.
+pub fn load_history(home: &Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(history_path(home)) else {
+        return Vec::new();
+    };
+    let mut history: Vec<String> = Vec::new();
+    for name in contents.lines().filter_map(normalize) {
+        if !history
+            .iter()
+            .any(|entry| command_key(entry) == command_key(&name))
+        {
+            history.push(name);
+        }
+    }
+    history.truncate(MAX_HISTORY_ENTRIES);
+    history
+}
+
+/// Complete `prefix` (e.g. `/rev` or `user:re`) to the commands available
+/// from `cwd`, as `scope:name`. Recently invoked commands come first, most
+/// recent first, followed by the rest in discovery order. The user scope and
+/// the history are resolved against `$HOME`.
+pub fn complete_command(prefix: &str, cwd: &Path) -> Vec<String> {
+    let history = home_dir()
+        .map(|home| load_history(&home))
+        .unwrap_or_default();
+    complete_command_in(prefix, &CommandRoots::for_cwd(cwd), &history)
+}
+
+/// Like [`complete_command`], with an explicit home directory for the user
+/// scope and the history (`None` disables both).
+pub fn complete_command_with_home(prefix: &str, cwd: &Path, home: Option<&Path>) -> Vec<String> {
+    let history = home.map(load_history).unwrap_or_default();
+    complete_command_in(prefix, &CommandRoots::new(cwd, home), &history)
+}
+
+/// Like [`complete_command`], but completing the commands in `roots` and
+/// ordering them by `history` (most recent first, as from
+/// [`load_history`]). An empty history keeps discovery order.
+///
+/// A command matches when its `scope:name` or, without a scope in `prefix`,
+/// its name starts with `prefix`, compared case-insensitively. A blank
+/// prefix matches every command, and a bare scope such as `/project:` every
+/// command of that scope; only that scope's directories are read. An
+/// unknown scope matches nothing.
+pub fn complete_command_in(prefix: &str, roots: &CommandRoots, history: &[String]) -> Vec<String> {
+    let prefix = prefix.trim();
+    let prefix = command_key(prefix.strip_prefix('/').unwrap_or(prefix));
+    let commands = match prefix.split_once(':') {
+        Some((scope, _)) => match Scope::from_prefix(scope) {
+            Some(scope) => discover_scope(roots, scope, &mut Vec::new()),
+            None => return Vec::new(),
+        },
+        None => discover_commands(roots),
+    };
+    let mut completions: Vec<(Option<usize>, String)> = commands
+        .into_iter()
+        .filter(|command| {
+            command_key(&command.full_name()).starts_with(&prefix)
+                || (!prefix.contains(':') && command.key.starts_with(&prefix))
+        })
+        .map(|command| {
+            let name = command.full_name();
+            let key = command_key(&name);
+            let recency = history.iter().position(|entry| command_key(entry) == key);
+            (recency, name)
+        })
+        .collect();
+    // Stable, so commands that were never invoked keep discovery order.
+    completions.sort_by_key(|(recency, _)| recency.unwrap_or(usize::MAX));
+    completions.into_iter().map(|(_, name)| name).collect()
+}
+
+/// The longest prefix shared by every one of `matches` (e.g. completions
+/// from [`complete_command_in`]), for a tab key that completes as far as is
+/// unambiguous. Characters are compared case-insensitively, like command
+/// names, and the prefix is spelled as in the first match. Empty when there
+/// are no matches or they share nothing.
+pub fn common_prefix(matches: &[String]) -> String {
+    let Some((first, rest)) = matches.split_first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for other in rest {
+        len = first[..len]
+            .char_indices()
+            .zip(other.chars())
+            .take_while(|((_, a), b)| a.to_lowercase().eq(b.to_lowercase()))
+            .last()
+            .map_or(0, |((index, c), _)| index + c.len_utf8());
+    }
+    first[..len].to_string()
+}
+
+/// A command as listed in a [`completion_manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionEntry {
+    /// `scope:name`, as completed after the leading `/`.
+    pub full_name: String,
+    /// The command's summary, as from
+    /// [`DiscoveredCommand::load_summary`](super::DiscoveredCommand::load_summary).
+    pub description: Option<String>,
+    /// Names of the `--name=value` options declared in the command's `args`
+    /// frontmatter, in declaration order.
+    pub arg_names: Vec<String>,
+}
+
+/// Every command available from `cwd`, in discovery order, with what a shell
+/// completion script generator needs to know about it. The user scope is
+/// resolved against `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn completion_manifest(cwd: &Path) -> Vec<CompletionEntry> {
+    completion_manifest_in(&CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`completion_manifest`], but listing the commands in `roots`. A
+/// command that cannot be read is listed without a description or
+/// arguments.
+pub fn completion_manifest_in(roots: &CommandRoots) -> Vec<CompletionEntry> {
+    discover_commands(roots)
+        .into_iter()
+        .map(|command| {
+            let mut entry = CompletionEntry {
+                full_name: command.full_name(),
+                description: None,
+                arg_names: Vec::new(),
+            };
+            if let Ok(found) = find_command(roots, command.scope, &command.name) {
+                let (frontmatter, body) = split_frontmatter(&found.contents);
+                entry.description = summary(body);
+                entry.arg_names = frontmatter
+                    .args
+                    .into_iter()
+                    .flatten()
+                    .map(|spec| spec.name)
+                    .collect();
+            }
+            entry
+        })
+        .collect()
+}
+
+fn history_path(home: &Path) -> PathBuf {
+    home.join(HISTORY_FILE)
+}
+
+/// `name` as `scope:name`, or `None` if it does not name a command.
+fn normalize(name: &str) -> Option<String> {
+    let name = name.trim();
+    let input = format!("/{}", name.strip_prefix('/').unwrap_or(name));
+    let invocation = parse_invocation(&input)?;
+    let scope = invocation.scope().ok()?;
+    if invocation.name.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{}:{}",
+        scope.as_str(),
+        escape_command_name(&invocation.name)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn the_manifest_lists_argument_names_from_the_schema() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "deploy.md",
+            "---\nargs: [{name: env, type: enum, values: [dev, prod]}, {name: retries}]\n---\n\
+             # Deploy the service\n\nDeploy to $1.\n",
+        );
+        write_command(cwd.path(), "fix.md", "Fix $ARGUMENTS");
+        write_command(cwd.path(), "blank.md", "");
+
+        assert_eq!(
+            completion_manifest_in(&CommandRoots::new(cwd.path(), None)),
+            vec![
+                CompletionEntry {
+                    full_name: "project:blank".to_string(),
+                    description: None,
+                    arg_names: Vec::new(),
+                },
+                CompletionEntry {
+                    full_name: "project:deploy".to_string(),
+                    description: Some("Deploy the service".to_string()),
+                    arg_names: vec!["env".to_string(), "retries".to_string()],
+                },
+                CompletionEntry {
+                    full_name: "project:fix".to_string(),
+                    description: Some("Fix $ARGUMENTS".to_string()),
+                    arg_names: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn recording_moves_a_command_to_the_front() {
+        let home = TempDir::new().unwrap();
+        assert_eq!(load_history(home.path()), Vec::<String>::new());
+
+        record_invocation_with_home("project:fix", home.path()).unwrap();
+        record_invocation_with_home("/review src/", home.path()).unwrap();
+        record_invocation_with_home("user:greet", home.path()).unwrap();
+        record_invocation_with_home("/project:Fix now", home.path()).unwrap();
+        record_invocation_with_home("team:nope", home.path()).unwrap();
+
+        assert_eq!(
+            load_history(home.path()),
+            vec![
+                "project:Fix".to_string(),
+                "user:greet".to_string(),
+                "project:review".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn completions_are_ordered_most_recently_used_first() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        for name in ["fix.md", "format.md", "review.md"] {
+            write_command(cwd.path(), name, "");
+        }
+        write_command(home.path(), "fixup.md", "");
+
+        let complete = |prefix| complete_command_with_home(prefix, cwd.path(), Some(home.path()));
+        assert_eq!(
+            complete("/f"),
+            vec!["project:fix", "project:format", "user:fixup"]
+        );
+
+        record_invocation_with_home("user:fixup", home.path()).unwrap();
+        record_invocation_with_home("format", home.path()).unwrap();
+        assert_eq!(
+            complete("/f"),
+            vec!["project:format", "user:fixup", "project:fix"]
+        );
+        assert_eq!(complete("user:"), vec!["user:fixup"]);
+        assert_eq!(complete("/project:R"), vec!["project:review"]);
+    }
+
+    #[test]
+    fn a_bare_scope_completes_to_every_command_in_it() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(home.path(), "greet.md", "");
+        write_command(home.path(), "fix.md", "");
+
+        let complete = |prefix| complete_command_with_home(prefix, cwd.path(), Some(home.path()));
+        assert_eq!(
+            complete("/project:"),
+            vec!["project:fix", "project:review__security"]
+        );
+        assert_eq!(complete("/user:"), vec!["user:fix", "user:greet"]);
+        assert_eq!(complete("User:"), vec!["user:fix", "user:greet"]);
+        assert_eq!(complete("/team:"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn common_prefix_is_what_every_match_starts_with() {
+        let strings =
+            |items: &[&str]| -> Vec<String> { items.iter().map(|item| item.to_string()).collect() };
+        assert_eq!(
+            common_prefix(&strings(&[
+                "project:review__security",
+                "project:Review__perf",
+                "project:review__",
+            ])),
+            "project:review__"
+        );
+        assert_eq!(common_prefix(&strings(&["project:fix", "user:fix"])), "");
+        assert_eq!(common_prefix(&strings(&["project:fix"])), "project:fix");
+        assert_eq!(
+            common_prefix(&strings(&["project:épée", "project:Épéiste"])),
+            "project:épé"
+        );
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn corrupt_history_is_ignored() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "lint.md", "");
+        let path = history_path(home.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        std::fs::write(&path, [0xff, 0xfe, b'\n']).unwrap();
+        assert_eq!(load_history(home.path()), Vec::<String>::new());
+        assert_eq!(
+            complete_command_with_home("", cwd.path(), Some(home.path())),
+            vec!["project:fix", "project:lint"]
+        );
+
+        std::fs::write(&path, "\n   \nteam:x\nproject:lint\n").unwrap();
+        assert_eq!(load_history(home.path()), vec!["project:lint".to_string()]);
+        record_invocation_with_home("fix", home.path()).unwrap();
+        assert_eq!(
+            load_history(home.path()),
+            vec!["project:fix".to_string(), "project:lint".to_string()]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Scaffolding a project's commands directory, e.g. while onboarding.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::Scope;
+
+/// Name of the sample command [`init_commands_dir`] creates.
+const EXAMPLE_FILE_NAME: &str = "example.md";
+
+const EXAMPLE_COMMAND: &str = "\
+# Explain something: `/example <topic>`
+
+Explain $ARGUMENTS in simple terms, with a short example.
+";
+
+/// Create the project commands directory of `project_root`,
+/// `.codex/commands/`, with a sample `example.md` command that uses
+/// `$ARGUMENTS`, and return its path. When the directory already exists it
+/// is returned as is, without adding the sample.
+pub fn init_commands_dir(project_root: &Path) -> std::io::Result<PathBuf> {
+    let dir = Scope::Project.commands_dir(project_root);
+    if dir.is_dir() {
+        return Ok(dir);
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(EXAMPLE_FILE_NAME), EXAMPLE_COMMAND)?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::expand_custom_command_with_home;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn creates_the_directory_with_a_working_example() {
+        let cwd = TempDir::new().unwrap();
+
+        let dir = init_commands_dir(cwd.path()).unwrap();
+        assert_eq!(dir, cwd.path().join(".codex/commands"));
+        let expanded = expand_custom_command_with_home("/example closures", cwd.path(), None);
+        assert_eq!(
+            expanded.unwrap().prompt,
+            "# Explain something: `/example <topic>`\n\n\
+             Explain closures in simple terms, with a short example.\n"
+        );
+    }
+
+    #[test]
+    fn an_existing_directory_is_left_alone() {
+        let cwd = TempDir::new().unwrap();
+        let fix = write_command(cwd.path(), "fix.md", "Fix");
+
+        let dir = init_commands_dir(cwd.path()).unwrap();
+        assert_eq!(dir, fix.parent().unwrap());
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec!["fix.md"]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Static checks on command files, for editors and other tooling.
+
+use super::frontmatter::split_frontmatter;
+use super::placeholders::ARGUMENTS_FROM_PLACEHOLDER;
+use super::placeholders::leading_index;
+
+/// Named placeholders substituted during expansion.
+const KNOWN_PLACEHOLDERS: [&str; 6] = ["ARGUMENTS", "INPUT", "CWD", "SELECTION", "BASE", "OS"];
+
+/// A problem found by [`lint_command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// Human-readable description of the problem.
+    pub message: String,
+    /// 1-based line of the offending text in the command file.
+    pub line: usize,
+    /// 1-based column, counted in characters, of the offending text.
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// `$NAME` looks like a placeholder but is not one, so it is sent to the
+    /// model verbatim.
+    UnknownPlaceholder,
+    /// `$0` or `$ARGUMENTS_FROM_0` (arguments are numbered from 1), which is
+    /// not substituted.
+    PositionalOutOfRange,
+}
+
+/// Check the contents of a command file for placeholders that will not be
+/// expanded. The frontmatter block, if any, is skipped, but positions are
+/// relative to the start of `contents`. Nothing is reported for `raw`
+/// commands, which are not substituted at all.
+pub fn lint_command(contents: &str) -> Vec<Lint> {
+    let (frontmatter, body) = split_frontmatter(contents);
+    if frontmatter.raw == Some(true) {
+        return Vec::new();
+    }
+    let skipped_lines = contents[..contents.len() - body.len()]
+        .matches('\n')
+        .count();
+
+    let mut lints = Vec::new();
+    for (index, line) in body.lines().enumerate() {
+        for (offset, placeholder) in placeholders(line) {
+            let Some((kind, message)) = check_placeholder(placeholder) else {
+                continue;
+            };
+            lints.push(Lint {
+                kind,
+                message,
+                line: skipped_lines + index + 1,
+                col: line[..offset].chars().count() + 1,
+            });
+        }
+    }
+    lints
+}
+
+/// Every `$` followed by an uppercase name or digits in `line`, with its
+/// byte offset. The returned text includes the `$`.
+pub(super) fn placeholders(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.match_indices('$')
+        .filter_map(move |(offset, _)| Some((offset, placeholder_at(&line[offset..])?)))
+}
+
+/// The `$` followed by an uppercase name or digits that `text` starts with.
+pub(super) fn placeholder_at(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix('$')?;
+    let len = match rest.chars().next() {
+        Some(c) if c.is_ascii_digit() => rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len()),
+        Some(c) if c.is_ascii_uppercase() => rest
+            .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+            .unwrap_or(rest.len()),
+        _ => return None,
+    };
+    Some(&text[..1 + len])
+}
+
+fn check_placeholder(placeholder: &str) -> Option<(LintKind, String)> {
+    let name = &placeholder[1..];
+    let index = match placeholder.strip_prefix(ARGUMENTS_FROM_PLACEHOLDER) {
+        Some(index) if index.starts_with(|c: char| c.is_ascii_digit()) => Some(index),
+        _ => name
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then_some(name),
+    };
+    if let Some(index) = index {
+        let in_range = leading_index(index).is_some();
+        return (!in_range).then(|| {
+            (
+                LintKind::PositionalOutOfRange,
+                format!("`{placeholder}` is out of range; arguments are numbered from 1"),
+            )
+        });
+    }
+    (!KNOWN_PLACEHOLDERS.contains(&name)).then(|| {
+        (
+            LintKind::UnknownPlaceholder,
+            format!("unknown placeholder `{placeholder}`"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reports_position_of_offending_placeholder() {
+        let contents =
+            "---\nnext: project:review\n---\nFix $ARGUMENTS.\n  Focus on $ARGS and $1.\n";
+
+        assert_eq!(
+            lint_command(contents),
+            vec![Lint {
+                kind: LintKind::UnknownPlaceholder,
+                message: "unknown placeholder `$ARGS`".to_string(),
+                line: 5,
+                col: 12,
+            }]
+        );
+    }
+
+    #[test]
+    fn raw_commands_are_not_linted() {
+        assert_eq!(lint_command("---\nraw: true\n---\n$ARGS $0"), Vec::new());
+    }
+
+    #[test]
+    fn flags_zero_positional_placeholder() {
+        let lints =
+            lint_command("é $10 $00 $0 $9 $INPUT $path $ARGUMENTS_FROM_2 $ARGUMENTS_FROM_0");
+
+        let found: Vec<(LintKind, usize, usize)> = lints
+            .iter()
+            .map(|lint| (lint.kind, lint.line, lint.col))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (LintKind::PositionalOutOfRange, 1, 7),
+                (LintKind::PositionalOutOfRange, 1, 11),
+                (LintKind::PositionalOutOfRange, 1, 48),
+            ]
+        );
+    }
+}
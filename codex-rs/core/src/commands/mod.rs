@@ -0,0 +1,1115 @@
+//! Custom slash commands backed by Markdown prompt files.
+//!
+//! Commands live under one of two roots:
+//!
+//! - project scope: `<cwd>/.codex/commands/`
+//! - user scope: `$HOME/.codex/commands/`
+//!
+//! Inside a Git repository the project scope also includes the
+//! `.codex/commands/` of every directory between `cwd` and the repository
+//! root, so a monorepo package sees both its own commands and the
+//! repository's. The nearest directory wins when two define the same name.
+//!
+//! Linked Git worktrees can opt in to also sharing the main working tree's
+//! commands ([`CommandRoots::with_shared_worktree_commands`]). An embedding
+//! such as an IDE can instead name the project explicitly
+//! ([`CommandRoots::with_workspace_root`]); only that directory's
+//! `.codex/commands/` is then in the project scope.
+//!
+//! Directories listed in `CODEX_COMMANDS_PATH` (separated like `PATH`) are
+//! searched after the user scope's own directories, as part of that scope.
+//! Only the entry points that resolve `$HOME` themselves (and
+//! [`CommandRoots::for_cwd`]) read it.
+//!
+//! The user scope can instead be configured as an ordered list of
+//! directories (see [`CommandRoots::with_user_dirs`]). They are searched in
+//! order, so when two directories define the same command the earlier one
+//! wins, both for expansion and for discovery. The user scope can also be
+//! disabled entirely ([`CommandRoots::without_user_scope`]).
+//!
+//! A file at `<root>/review/security.md` is invoked as
+//! `/project:review__security` (or `/user:review__security`). A bare
+//! `/review__security` resolves against the project scope, or the user
+//! scope when only that has the command. A `:` that
+//! is part of a file name is written `\:`, e.g. `/project:weird\:name`.
+//! In a run of more than two underscores only the last two separate
+//! directories, so `a___b` is `a_/b.md` and `a____b` is `a__/b.md`.
+//! Names are matched case-insensitively, whatever the filesystem, but are
+//! displayed as spelled on disk.
+//!
+//! A command can have environment variants: `deploy.prod.md` is the `prod`
+//! variant of `deploy`. An `--env=prod` among the leading options (or,
+//! without one, `CODEX_COMMAND_ENV=prod`) makes `/deploy` use the variant
+//! when it exists and the base file otherwise. The flag is not part of
+//! `$ARGUMENTS`. The `default_args` frontmatter key supplies arguments that
+//! are prepended to the user's, and may itself select a variant.
+//! Variants are ordinary commands too, so `/deploy.prod` also works.
+//!
+//! Files and directories whose name starts with `_` are partials: they can
+//! be included by other commands but are not listed by discovery. A
+//! `_group.md` or `README.md` in a subdirectory describes that directory's
+//! group of commands (see [`CommandGroup`]) and is not a command either.
+//!
+//! A command file may start with a `---` frontmatter block of metadata (see
+//! [`Frontmatter`]), which is stripped from the prompt. Its `template` key,
+//! if present, is used as the prompt in place of the body. A `config.toml`
+//! in a commands directory sets defaults for every command in it.
+//!
+//! A command body may inline a partial with a line of the form
+//! `@include <path>`. The path is relative to the file containing the
+//! directive and must stay inside the commands root; `@include-if <path>`
+//! does the same but silently skips a partial that does not exist. A path
+//! starting with `~/` (e.g. `@include ~/review-standards.md`) is instead
+//! relative to the user scope's commands directory, `~/.codex/commands` by
+//! default, and must stay inside that directory.
+//! Placeholders are substituted only after every include has been resolved,
+//! so partials see the same arguments as the command that was invoked:
+//!
+//! - `$ARGUMENTS` expands to everything after the command name.
+//! - `$INPUT` expands to the whole input, including the leading `/` and the
+//!   command name (e.g. `/project:echo hello`).
+//! - `$CWD` expands to the working directory the command was invoked from.
+//! - `$OS` expands to the operating system Codex was built for: `linux`,
+//!   `macos`, `windows`, or another [`std::env::consts::OS`] value. It is
+//!   not substituted when followed by a letter, digit or `_` (`$OSTYPE`).
+//! - `$SELECTION` expands to the text the caller passes as
+//!   [`ExpandOptions::selection`], e.g. the selection in an editor buffer.
+//! - `${NAME}` expands to the session variable `NAME` from
+//!   [`ExpandOptions::vars`], else to the environment variable `NAME`, or to
+//!   the empty string when neither is set (an error with `strict_env`).
+//! - `$1`, `$2`, … expand to the individual whitespace-separated arguments
+//!   (or to the empty string when fewer arguments were given). The index is
+//!   every digit after the `$`, so `$10` is the tenth argument. They may be
+//!   embedded in other text (`v$1-beta`).
+//! - `${ARGUMENTS:shellquote}`, `${1:shellquote}` and the like expand to the
+//!   value of `$ARGUMENTS`, `$1`, … quoted as a single POSIX shell word, so
+//!   that a template building a shell command cannot be injected into.
+//! - `$ARGUMENTS_FROM_2`, `$ARGUMENTS_FROM_3`, … expand to the arguments from
+//!   that index on, joined with single spaces (or to the empty string when
+//!   there are fewer), e.g. the free text after a leading `$1` mode.
+//!
+//! A line of the form `@run <command> [args]`, e.g. `@run project:lint $1`,
+//! is replaced by the prompt of another command: the arguments are
+//! substituted first, then the named command is expanded with them as if
+//! invoked on its own, frontmatter and placeholders included, and its
+//! prompt is inlined. Only that prompt is used; its `next:` is ignored.
+//! Commands may `@run` each other up to the include depth, but not in a
+//! cycle.
+//!
+//! A command whose frontmatter has `extends: user:base-review` builds on
+//! that base command: the base is expanded with the same arguments and its
+//! prompt replaces each `$BASE` in the command's own, or, without a
+//! `$BASE`, comes before it. Bases may extend other commands, but not in a
+//! cycle.
+//!
+//! A renamed command can keep its old file with `deprecated_redirect:
+//! project:newname`: invoking the old name expands the successor with the
+//! same arguments and warns with [`ExpandWarning::Deprecated`].
+//!
+//! A command whose frontmatter has `visibility: [codex, /home/me/work/*]` is
+//! listed by discovery only in the projects it names, e.g. a personal user
+//! command that only makes sense in some repositories. A project is
+//! identified by its root, as for `@context` below: an entry without a `/`
+//! is matched against the root's directory name and one with a `/` against
+//! its whole (canonical) path. Entries may use `*` and `?`, and paths `**`.
+//! A command that is not listed can still be invoked by name.
+//!
+//! A line of the form `@context <path>` (e.g. `@context src/lib.rs`) inlines
+//! a project file for context. Unlike `@include`, the path is relative to the
+//! working directory, may use placeholders (`@context $1`), and must stay
+//! inside the project root ([`CommandRoots::project_root`]): the repository
+//! root or, outside a repository, the working directory. The file is
+//! inserted as is, without substituting anything in it.
+//!
+//! With the `commands-archive` feature, a commands directory that does not
+//! exist can be shipped as a `commands.zip` archive next to it instead; the
+//! directory wins when both exist. With the `commands-watch` feature,
+//! [`watch`] reports changes to command files as they happen.
+//!
+//! Substitution itself does no I/O and is available on its own as
+//! [`substitute_placeholders`].
+//!
+//! [`lint_command`] reports placeholders that will not be expanded, with
+//! their position in the file.
+//!
+//! Callers that want completions ordered by use can [`record_invocation`]
+//! each command they run; [`complete_command`] then lists recently used
+//! commands first.
+//!
+//! Nothing in this module depends on a particular front end: the TUI and any
+//! headless caller share the same resolution, expansion and discovery logic.
+
+#[cfg(feature = "commands-archive")]
+mod archive;
+mod cache;
+mod config_file;
+mod discover;
+mod error;
+mod expand;
+mod export;
+mod frontmatter;
+mod glob;
+mod history;
+mod init;
+mod lint;
+mod placeholders;
+mod postprocess;
+mod preview;
+mod sanitize;
+mod schema;
+mod search;
+mod stats;
+mod validate;
+#[cfg(feature = "commands-watch")]
+mod watch;
+
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub use cache::CommandCache;
+use config_file::CommandsConfig;
+use config_file::load_commands_config;
+pub use discover::CommandGroup;
+pub use discover::Discovered;
+pub use discover::DiscoveredCommand;
+pub use discover::DiscoveryDiagnostic;
+pub use discover::DiscoveryOptions;
+pub use discover::by_display_order;
+pub use discover::discover_custom_commands;
+pub use discover::discover_custom_commands_for;
+pub use discover::discover_custom_commands_grouped;
+pub use discover::discover_custom_commands_in;
+pub use discover::discover_custom_commands_with_home;
+pub use discover::discover_custom_commands_with_options;
+pub use discover::path_to_command_name;
+pub use error::ExpandError;
+pub use expand::ExpandOptions;
+pub use expand::ExpandWarning;
+pub use expand::ExpandedCommand;
+pub use expand::TraceStep;
+pub use expand::expand;
+pub use expand::expand_custom_command;
+pub use expand::expand_custom_command_in;
+pub use expand::expand_custom_command_with_fallback;
+pub use expand::expand_custom_command_with_home;
+pub use expand::expand_custom_command_with_options;
+pub use expand::expand_file;
+pub use expand::expand_file_in;
+pub use expand::expand_in;
+pub use expand::expand_with_trace;
+pub use export::export_all;
+pub use export::export_all_in;
+pub use frontmatter::Frontmatter;
+pub use frontmatter::FrontmatterError;
+pub use glob::expand_glob;
+pub use glob::expand_glob_in;
+pub use history::CompletionEntry;
+pub use history::common_prefix;
+pub use history::complete_command;
+pub use history::complete_command_in;
+pub use history::complete_command_with_home;
+pub use history::completion_manifest;
+pub use history::completion_manifest_in;
+pub use history::load_history;
+pub use history::record_invocation;
+pub use history::record_invocation_with_home;
+pub use init::init_commands_dir;
+pub use lint::Lint;
+pub use lint::LintKind;
+pub use lint::lint_command;
+pub use placeholders::substitute_placeholders;
+pub use postprocess::PostProcess;
+pub use preview::CommandPreview;
+pub use preview::explain_command;
+pub use preview::preview;
+pub use preview::preview_in;
+pub use sanitize::sanitize_command_name;
+pub use schema::ArgSpec;
+pub use schema::ArgType;
+pub use search::search_commands;
+pub use search::search_commands_in;
+pub use search::search_commands_with_home;
+pub use stats::CommandStats;
+pub use stats::available_scopes;
+pub use stats::available_scopes_in;
+pub use stats::command_stats;
+pub use validate::ValidationIssue;
+pub use validate::ValidationReport;
+pub use validate::validate_all;
+pub use validate::validate_all_in;
+#[cfg(feature = "commands-watch")]
+pub use watch::CommandChange;
+#[cfg(feature = "commands-watch")]
+pub use watch::CommandChangeKind;
+#[cfg(feature = "commands-watch")]
+pub use watch::watch;
+#[cfg(feature = "commands-watch")]
+pub use watch::watch_in;
+
+/// Location of the project commands directory relative to the working
+/// directory (or one of its ancestors, see [`CommandRoots::new`]).
+pub const PROJECT_COMMANDS_DIR: &str = ".codex/commands";
+
+/// Location of the user commands directory relative to `$HOME`.
+pub const USER_COMMANDS_DIR: &str = ".codex/commands";
+
+/// Command names encode nested directories with this separator, e.g.
+/// `review/security.md` becomes `review__security`.
+const PATH_SEPARATOR_ENCODING: &str = "__";
+
+/// Escaped form of `:` inside a command name, so that `/project:a\:b`
+/// addresses the file `a:b.md` instead of splitting on the second colon.
+const ESCAPED_COLON: &str = "\\:";
+
+/// Extension (without the dot) of command files.
+const COMMAND_FILE_EXTENSION: &str = "md";
+
+/// Longest file or directory name, in bytes, a command name may map to; the
+/// limit of common filesystems.
+const MAX_NAME_COMPONENT_BYTES: usize = 255;
+
+/// Maximum nesting of `@include` directives before expansion gives up.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Command files (and partials) larger than this are rejected instead of
+/// being sent to the model, unless the commands directory's `config.toml`
+/// sets another `max_file_bytes`.
+const MAX_COMMAND_FILE_BYTES: u64 = 256 * 1024;
+
+/// Prompts that grow larger than this once their `@include` and `@run`
+/// directives are expanded are rejected, unless the commands directory's
+/// `config.toml` sets another `max_expansion_bytes`.
+const MAX_EXPANSION_BYTES: u64 = 1024 * 1024;
+
+/// Where a custom command is looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Scope {
+    /// `<cwd>/.codex/commands/`
+    Project,
+    /// `$HOME/.codex/commands/`
+    User,
+}
+
+impl Scope {
+    /// Every scope, in lookup and listing order.
+    pub const ALL: [Scope; 2] = [Scope::Project, Scope::User];
+
+    /// The prefix used before `:` when invoking a command in this scope.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Project => "project",
+            Scope::User => "user",
+        }
+    }
+
+    /// Parse the prefix before `:` in `/scope:name`.
+    pub fn from_prefix(prefix: &str) -> Option<Scope> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == prefix)
+    }
+
+    /// The commands directory of this scope under `base`, a project
+    /// directory or the home directory, e.g. `<base>/.codex/commands`. Both
+    /// discovery and expansion build their roots with this.
+    pub fn commands_dir(self, base: &Path) -> PathBuf {
+        match self {
+            Scope::Project => base.join(PROJECT_COMMANDS_DIR),
+            Scope::User => base.join(USER_COMMANDS_DIR),
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    home_from_env(std::env::var_os("HOME"))
+}
+
+/// Interpret the value of `HOME`. An empty value is treated like an unset
+/// one: joining onto an empty path would silently produce a path relative to
+/// the process working directory.
+fn home_from_env(value: Option<OsString>) -> Option<PathBuf> {
+    value.filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// Environment variable listing extra commands directories, separated like
+/// `PATH` (`:` on Unix, `;` on Windows).
+pub const COMMANDS_PATH_ENV_VAR: &str = "CODEX_COMMANDS_PATH";
+
+/// The directories listed in `CODEX_COMMANDS_PATH`, empty entries skipped.
+fn commands_path_from_env(value: Option<OsString>) -> Vec<PathBuf> {
+    value
+        .map(|value| {
+            std::env::split_paths(&value)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The commands directories of each scope, in precedence order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandRoots {
+    cwd: PathBuf,
+    project: Vec<PathBuf>,
+    user: Vec<PathBuf>,
+    /// How many entries at the end of `user` come from
+    /// [`with_commands_path_dirs`](Self::with_commands_path_dirs).
+    commands_path_dirs: usize,
+    /// Set by [`with_workspace_root`](Self::with_workspace_root).
+    workspace_root: Option<PathBuf>,
+}
+
+impl CommandRoots {
+    /// The default roots: `<dir>/.codex/commands` for `cwd` and, inside a
+    /// Git repository, each of its ancestors up to the repository root
+    /// (nearest first) for the project scope, and, when `home` is given,
+    /// `<home>/.codex/commands` for the user scope.
+    pub fn new(cwd: &Path, home: Option<&Path>) -> Self {
+        Self {
+            cwd: cwd.to_path_buf(),
+            project: project_dirs(cwd, home),
+            user: home
+                .map(|home| Scope::User.commands_dir(home))
+                .into_iter()
+                .collect(),
+            commands_path_dirs: 0,
+            workspace_root: None,
+        }
+    }
+
+    /// Like [`CommandRoots::new`], with the user scope resolved against
+    /// `$HOME` and extended with the directories in `CODEX_COMMANDS_PATH`.
+    pub fn for_cwd(cwd: &Path) -> Self {
+        Self::new(cwd, home_dir().as_deref()).with_commands_path_dirs(commands_path_from_env(
+            std::env::var_os(COMMANDS_PATH_ENV_VAR),
+        ))
+    }
+
+    /// Replace the user scope with `dirs`, searched in order. Each entry is a
+    /// commands directory itself; `.codex/commands` is not appended. Any
+    /// [`with_commands_path_dirs`](Self::with_commands_path_dirs) are kept
+    /// after them.
+    pub fn with_user_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        let commands_path = self
+            .user
+            .split_off(self.user.len() - self.commands_path_dirs);
+        self.user = dirs;
+        self.user.extend(commands_path);
+        self
+    }
+
+    /// Append `dirs` (as from `CODEX_COMMANDS_PATH`) to the user scope, after
+    /// every other user directory, so that a command defined in both a user
+    /// directory and one of `dirs` comes from the former.
+    pub fn with_commands_path_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.commands_path_dirs += dirs.len();
+        self.user.extend(dirs);
+        self
+    }
+
+    /// Disable the user scope, including any `CODEX_COMMANDS_PATH`
+    /// directories: `/user:` commands fail with
+    /// [`ExpandError::UnknownScope`] and discovery lists no user commands.
+    pub fn without_user_scope(mut self) -> Self {
+        self.user.clear();
+        self.commands_path_dirs = 0;
+        self
+    }
+
+    /// Inside a Git worktree, also search the `.codex/commands` of the main
+    /// working tree (the parent of the repository's common directory, as
+    /// `git rev-parse --git-common-dir` reports it), after every other
+    /// project directory, so that all worktrees share its commands. Does
+    /// nothing outside a linked worktree.
+    pub fn with_shared_worktree_commands(mut self) -> Self {
+        let shared = Some(self.project_root())
+            .filter(|dir| dir.join(".git").exists())
+            .and_then(|dir| git_common_dir(&dir.join(".git")))
+            .and_then(|common| Some(Scope::Project.commands_dir(common.parent()?)));
+        if let Some(shared) = shared
+            && !self.project.contains(&shared)
+        {
+            self.project.push(shared);
+        }
+        self
+    }
+
+    /// Use `workspace_root`, e.g. the folder opened in an IDE, as the
+    /// project instead of the one derived from the working directory: the
+    /// project scope becomes `<workspace_root>/.codex/commands` alone, and
+    /// `@context` files and `visibility` are resolved against
+    /// `workspace_root`. The working directory is still `$CWD`, and
+    /// `@context` paths are still relative to it.
+    pub fn with_workspace_root(mut self, workspace_root: &Path) -> Self {
+        self.project = vec![Scope::Project.commands_dir(workspace_root)];
+        self.workspace_root = Some(workspace_root.to_path_buf());
+        self
+    }
+
+    /// The working directory the roots were resolved from.
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// The root of the project: the
+    /// [workspace root](Self::with_workspace_root) if one is set, else the
+    /// root of the Git repository the working directory is in or, outside a
+    /// repository, the working directory itself.
+    pub fn project_root(&self) -> &Path {
+        self.workspace_root
+            .as_deref()
+            .unwrap_or_else(|| project_root(&self.cwd))
+    }
+
+    /// Commands directories for `scope`, highest precedence first. Empty
+    /// when the scope has no root (e.g. `HOME` is not set).
+    pub fn dirs(&self, scope: Scope) -> &[PathBuf] {
+        match scope {
+            Scope::Project => &self.project,
+            Scope::User => &self.user,
+        }
+    }
+}
+
+/// `<dir>/.codex/commands` for `cwd` and every ancestor up to the nearest one
+/// containing `.git` (a directory, or a file for worktrees and submodules).
+/// Outside a repository only `cwd` is used. `home` is skipped, since its
+/// commands directory is the user scope.
+fn project_dirs(cwd: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for dir in cwd.ancestors() {
+        if dirs.is_empty() || Some(dir) != home {
+            dirs.push(Scope::Project.commands_dir(dir));
+        }
+        if dir.join(".git").exists() {
+            return dirs;
+        }
+    }
+    vec![Scope::Project.commands_dir(cwd)]
+}
+
+/// The common directory of the repository whose `.git` is `dot_git`, when
+/// it is the `gitdir:` file of a linked worktree: the directory named by
+/// the `commondir` file in the worktree's own Git directory.
+fn git_common_dir(dot_git: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(dot_git).ok()?;
+    let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+    let git_dir = dot_git.parent()?.join(git_dir);
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir")).ok()?;
+    git_dir.join(common_dir.trim()).canonicalize().ok()
+}
+
+/// The root of the Git repository `cwd` is in: the nearest ancestor (or
+/// `cwd` itself) containing `.git`. Outside a repository, `cwd`.
+fn project_root(cwd: &Path) -> &Path {
+    cwd.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(cwd)
+}
+
+/// A command file located in one of a scope's roots.
+struct FoundCommand {
+    /// The commands directory (or archive) the file was found in.
+    root: PathBuf,
+    /// Settings from that directory's `config.toml`.
+    config: CommandsConfig,
+    path: PathBuf,
+    contents: String,
+    /// Whether `path` is a file on disk, whose partials can be included.
+    /// Commands read from an archive or supplied by a fallback are not.
+    on_disk: bool,
+}
+
+/// Look `name` up in each root of `scope` in turn and return the first
+/// match. Fails with [`ExpandError::FileNotFound`] (naming the file in the
+/// highest-precedence root) when no root has it, or with
+/// [`ExpandError::NoCommandsDir`] when none of the roots exists.
+fn find_command(
+    roots: &CommandRoots,
+    scope: Scope,
+    name: &str,
+) -> Result<FoundCommand, ExpandError> {
+    let dirs = roots.dirs(scope);
+    let Some(first) = dirs.first() else {
+        return Err(ExpandError::UnknownScope(scope.as_str().to_string()));
+    };
+    let mut any_root_exists = false;
+    for root in dirs {
+        let exact = command_path(root, name)?;
+        #[cfg(feature = "commands-archive")]
+        if let Some(archive) = archive::CommandArchive::for_root(root)? {
+            any_root_exists = true;
+            match archive.find(name)? {
+                Some(found) => return Ok(found),
+                None => continue,
+            }
+        }
+        if !root.is_dir() {
+            continue;
+        }
+        any_root_exists = true;
+        let path = if exact.is_file() {
+            Some(exact)
+        } else {
+            discover::find_case_insensitive(root, name)
+        };
+        let Some(path) = path else {
+            continue;
+        };
+        let config = load_commands_config(root)?;
+        if let Some(contents) = read_command_file(&path, config.max_file_bytes)? {
+            return Ok(FoundCommand {
+                root: root.clone(),
+                config,
+                path,
+                contents,
+                on_disk: true,
+            });
+        }
+    }
+    if !any_root_exists {
+        return Err(ExpandError::NoCommandsDir(first.clone()));
+    }
+    Err(ExpandError::FileNotFound(command_path(first, name)?))
+}
+
+/// Split a `scope:name` token on its first unescaped `:` and unescape the
+/// name. Tokens without an unescaped `:` have no scope.
+fn split_command_token(token: &str) -> (Option<&str>, Cow<'_, str>) {
+    let separator = token
+        .char_indices()
+        .find(|&(i, c)| c == ':' && !token[..i].ends_with('\\'))
+        .map(|(i, _)| i);
+    let (scope, name) = match separator {
+        Some(i) => (Some(&token[..i]), &token[i + 1..]),
+        None => (None, token),
+    };
+    let name = if name.contains(ESCAPED_COLON) {
+        Cow::Owned(name.replace(ESCAPED_COLON, ":"))
+    } else {
+        Cow::Borrowed(name)
+    };
+    (scope, name)
+}
+
+/// Inverse of the unescaping in [`split_command_token`], for displaying a
+/// name after its scope prefix.
+fn escape_command_name(name: &str) -> Cow<'_, str> {
+    if name.contains(':') {
+        Cow::Owned(name.replace(':', ESCAPED_COLON))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Map a command name to its file, rejecting names that could escape the
+/// scope root.
+fn command_path(root: &Path, name: &str) -> Result<PathBuf, ExpandError> {
+    if !is_valid_command_name(name) {
+        return Err(ExpandError::InvalidName(name.to_string()));
+    }
+
+    let segments = name_segments(name);
+    // The file name gets the extension. Checked here so that an absurdly
+    // long name fails clearly instead of as an I/O error.
+    let too_long = segments.iter().enumerate().any(|(i, segment)| {
+        let extension = if i + 1 == segments.len() {
+            COMMAND_FILE_EXTENSION.len() + 1
+        } else {
+            0
+        };
+        segment.len() + extension > MAX_NAME_COMPONENT_BYTES
+    });
+    if too_long {
+        return Err(ExpandError::NameTooLong(name.to_string()));
+    }
+
+    let relative = segments.join(std::path::MAIN_SEPARATOR_STR);
+    Ok(root.join(format!("{relative}.{COMMAND_FILE_EXTENSION}")))
+}
+
+/// Split a command name into its path segments.
+///
+/// Every run of two or more underscores is one separator made of the run's
+/// last two underscores; any before them stay in the preceding segment. This
+/// is the inverse of how discovery names nested files (directory name, `__`,
+/// file name), and it means a segment after a separator never starts with
+/// `_`, so a partial cannot be addressed by name.
+fn name_segments(name: &str) -> Vec<&str> {
+    let bytes = name.as_bytes();
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let run = bytes[i..].iter().take_while(|b| **b == b'_').count();
+        if run >= PATH_SEPARATOR_ENCODING.len() {
+            segments.push(&name[start..i + run - PATH_SEPARATOR_ENCODING.len()]);
+            start = i + run;
+        }
+        i += run.max(1);
+    }
+    segments.push(&name[start..]);
+    segments
+}
+
+/// Whether `name` (the part after `scope:`) maps to a path inside the scope
+/// root.
+fn is_valid_command_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(['/', '\\'])
+        && name_segments(name)
+            .iter()
+            .all(|segment| !segment.is_empty() && !segment.starts_with('.'))
+}
+
+/// Case-folded form of a command name, used to compare names the same way on
+/// case-sensitive and case-insensitive filesystems.
+fn command_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Inverse of [`command_path`] for the last path segment: the part of a
+/// command file's name that goes into the command name, or `None` if it is
+/// not a command file.
+fn command_file_stem(file_name: &str) -> Option<&str> {
+    // Remove exactly one extension: `notes.md.md` is the command `notes.md`.
+    let file_name = Path::new(file_name);
+    if file_name.extension()? != COMMAND_FILE_EXTENSION {
+        return None;
+    }
+    file_name.file_stem()?.to_str()
+}
+
+/// Read a command file, returning `Ok(None)` when it does not exist and an
+/// error when it is larger than `max_bytes`. The contents are normalized
+/// with [`normalize_contents`]; every command file and partial is read
+/// through here.
+fn read_command_file(path: &Path, max_bytes: u64) -> Result<Option<String>, ExpandError> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ExpandError::Io(path.to_path_buf(), e)),
+    };
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    if metadata.len() > max_bytes {
+        return Err(ExpandError::FileTooLarge(path.to_path_buf(), max_bytes));
+    }
+    std::fs::read_to_string(path)
+        .map(|contents| Some(normalize_contents(contents)))
+        .map_err(|e| ExpandError::Io(path.to_path_buf(), e))
+}
+
+/// Drop a leading UTF-8 byte order mark, which some editors write, so that
+/// it does not end up in the first line (hiding a frontmatter delimiter or
+/// prefixing the summary).
+fn normalize_contents(contents: String) -> String {
+    match contents.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => contents,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::test_support::write_command;
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn empty_home_disables_user_scope() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "fix it");
+
+        let home = home_from_env(Some(OsString::new()));
+        assert_eq!(home, None);
+
+        // With `HOME=""` the user scope must be skipped, not resolved as
+        // `./.codex/commands` (which here would be the project directory).
+        assert_eq!(
+            discover_custom_commands_with_home(cwd.path(), home.as_deref()),
+            vec!["project:fix".to_string()]
+        );
+        let err =
+            expand_custom_command_with_home("/user:fix", cwd.path(), home.as_deref()).unwrap_err();
+        assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "user"));
+    }
+
+    #[test]
+    fn earlier_user_root_wins_on_conflicts() {
+        let cwd = TempDir::new().unwrap();
+        let primary = TempDir::new().unwrap();
+        let dotfiles = TempDir::new().unwrap();
+        let write = |dir: &TempDir, name: &str, contents: &str| {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        };
+        write(&primary, "review.md", "primary review");
+        write(&dotfiles, "review.md", "dotfiles review");
+        write(&dotfiles, "deploy.md", "dotfiles deploy");
+
+        let roots = CommandRoots::new(cwd.path(), None).with_user_dirs(vec![
+            primary.path().to_path_buf(),
+            dotfiles.path().to_path_buf(),
+        ]);
+
+        let expand = |input: &str| expand_custom_command_in(input, &roots).unwrap().prompt;
+        assert_eq!(expand("/user:review"), "primary review");
+        assert_eq!(expand("/user:deploy"), "dotfiles deploy");
+
+        assert_eq!(
+            discover_custom_commands_in(&roots),
+            vec!["user:deploy".to_string(), "user:review".to_string()]
+        );
+        let review = search_commands_in("review", &roots);
+        assert_eq!(review.len(), 1);
+        assert_eq!(review[0].path, primary.path().join("review.md"));
+
+        let err = expand_custom_command_in("/user:missing", &roots).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::FileNotFound(path) if path.starts_with(primary.path())),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn ancestor_commands_are_merged_up_to_the_repo_root() {
+        let outside = TempDir::new().unwrap();
+        let repo = outside.path().join("repo");
+        let package = repo.join("packages/web");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&package).unwrap();
+        write_command(outside.path(), "outside.md", "not in the repo");
+        write_command(&repo, "fix.md", "repo fix");
+        write_command(&repo, "lint.md", "repo lint");
+        write_command(&package, "fix.md", "package fix");
+
+        let roots = CommandRoots::new(&package, None);
+        assert_eq!(roots.dirs(Scope::Project).len(), 3);
+        assert_eq!(
+            discover_custom_commands_in(&roots),
+            vec!["project:fix".to_string(), "project:lint".to_string()]
+        );
+        for (input, prompt) in [("/fix", "package fix"), ("/lint", "repo lint")] {
+            let expanded = expand_custom_command_in(input, &roots).unwrap();
+            assert_eq!(expanded.prompt, prompt, "{input}");
+        }
+
+        // Outside a repository only the working directory is searched.
+        assert_eq!(
+            CommandRoots::new(outside.path(), None).dirs(Scope::Project),
+            [Scope::Project.commands_dir(outside.path())]
+        );
+    }
+
+    #[test]
+    fn worktrees_can_share_the_main_checkouts_commands() {
+        let outside = TempDir::new().unwrap();
+        let main = outside.path().join("repo");
+        let worktree = outside.path().join("repo-feature");
+        let git_dir = main.join(".git/worktrees/repo-feature");
+        std::fs::create_dir_all(&git_dir).unwrap();
+        std::fs::create_dir_all(&worktree).unwrap();
+        std::fs::write(git_dir.join("commondir"), "../..\n").unwrap();
+        std::fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", git_dir.display()),
+        )
+        .unwrap();
+        write_command(&main, "fix.md", "shared fix");
+        write_command(&main, "lint.md", "shared lint");
+        write_command(&worktree, "fix.md", "worktree fix");
+
+        let roots = CommandRoots::new(&worktree, None);
+        assert_eq!(roots.dirs(Scope::Project).len(), 1);
+        assert!(expand_custom_command_in("/lint", &roots).is_err());
+
+        let roots = roots.with_shared_worktree_commands();
+        assert_eq!(
+            roots.dirs(Scope::Project)[1],
+            Scope::Project.commands_dir(&main.canonicalize().unwrap())
+        );
+        for (input, prompt) in [("/fix", "worktree fix"), ("/lint", "shared lint")] {
+            let expanded = expand_custom_command_in(input, &roots).unwrap();
+            assert_eq!(expanded.prompt, prompt, "{input}");
+        }
+
+        // The main checkout itself has nothing to share with.
+        let roots = CommandRoots::new(&main, None).with_shared_worktree_commands();
+        assert_eq!(roots.dirs(Scope::Project).len(), 1);
+    }
+
+    #[test]
+    fn a_workspace_root_replaces_the_project_of_the_cwd() {
+        let outside = TempDir::new().unwrap();
+        let workspace = outside.path().join("workspace");
+        let cwd = outside.path().join("repo/src");
+        std::fs::create_dir_all(outside.path().join("repo/.git")).unwrap();
+        std::fs::create_dir_all(&cwd).unwrap();
+        write_command(&workspace, "build.md", "Build in $CWD");
+        write_command(&workspace, "notes.md", "@context ../workspace/notes.txt\n");
+        std::fs::write(workspace.join("notes.txt"), "workspace notes").unwrap();
+        write_command(outside.path().join("repo").as_path(), "fix.md", "Fix");
+
+        let roots = CommandRoots::new(&cwd, None).with_workspace_root(&workspace);
+        assert_eq!(roots.project_root(), workspace);
+        assert_eq!(
+            discover_custom_commands_in(&roots),
+            vec!["project:build", "project:notes"]
+        );
+        assert_eq!(
+            expand_custom_command_in("/build", &roots).unwrap().prompt,
+            format!("Build in {}", cwd.display())
+        );
+        assert!(matches!(
+            expand_custom_command_in("/fix", &roots),
+            Err(ExpandError::FileNotFound(_))
+        ));
+
+        // `@context` paths are relative to the cwd but confined to the
+        // workspace.
+        let roots =
+            CommandRoots::new(&outside.path().join("repo"), None).with_workspace_root(&workspace);
+        assert_eq!(
+            expand_custom_command_in("/notes", &roots).unwrap().prompt,
+            "workspace notes\n"
+        );
+    }
+
+    #[test]
+    fn discovery_and_expansion_use_the_scope_commands_dirs() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "project fix");
+        write_command(home.path(), "greet.md", "user greet");
+
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+        for (scope, base) in [(Scope::Project, cwd.path()), (Scope::User, home.path())] {
+            assert_eq!(roots.dirs(scope), [scope.commands_dir(base)]);
+        }
+        for command in discover::discover_commands(&roots) {
+            let base = match command.scope {
+                Scope::Project => cwd.path(),
+                Scope::User => home.path(),
+            };
+            assert_eq!(
+                command.path,
+                command
+                    .scope
+                    .commands_dir(base)
+                    .join(&command.relative_path)
+            );
+            let expanded =
+                expand_custom_command_in(&format!("/{}", command.full_name()), &roots).unwrap();
+            assert_eq!(
+                expanded.prompt,
+                std::fs::read_to_string(&command.path).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn commands_path_dirs_extend_the_user_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let shared = TempDir::new().unwrap();
+        write_command(home.path(), "review.md", "home review");
+        std::fs::write(shared.path().join("review.md"), "shared review").unwrap();
+        std::fs::write(shared.path().join("lint.md"), "shared lint").unwrap();
+
+        let value = std::env::join_paths([Path::new(""), shared.path()]).unwrap();
+        let dirs = commands_path_from_env(Some(value));
+        assert_eq!(dirs, vec![shared.path().to_path_buf()]);
+
+        let roots = CommandRoots::new(cwd.path(), Some(home.path())).with_commands_path_dirs(dirs);
+        assert_eq!(
+            discover_custom_commands_in(&roots),
+            vec!["user:lint".to_string(), "user:review".to_string()]
+        );
+        for (input, prompt) in [
+            ("/user:review", "home review"),
+            ("/user:lint", "shared lint"),
+        ] {
+            assert_eq!(
+                expand_custom_command_in(input, &roots).unwrap().prompt,
+                prompt
+            );
+        }
+
+        // Configured user directories replace `$HOME`, not the path entries.
+        let roots = roots.with_user_dirs(Vec::new());
+        assert_eq!(roots.dirs(Scope::User), [shared.path().to_path_buf()]);
+        assert!(roots.without_user_scope().dirs(Scope::User).is_empty());
+    }
+
+    #[test]
+    fn disabled_user_scope_is_unknown_and_not_listed() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "fix it");
+        write_command(home.path(), "hello.md", "hello");
+
+        let roots = CommandRoots::new(cwd.path(), Some(home.path())).without_user_scope();
+        assert_eq!(
+            discover_custom_commands_in(&roots),
+            vec!["project:fix".to_string()]
+        );
+        let err = expand_custom_command_in("/user:hello", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "user"));
+        assert_eq!(
+            expand_custom_command_in("/project:fix", &roots)
+                .unwrap()
+                .prompt,
+            "fix it"
+        );
+    }
+
+    #[test]
+    fn escaped_colons_stay_in_the_name() {
+        assert_eq!(
+            split_command_token("project:weird\\:name"),
+            (Some("project"), Cow::Borrowed("weird:name"))
+        );
+        assert_eq!(
+            split_command_token("weird\\:name"),
+            (None, Cow::Borrowed("weird:name"))
+        );
+        assert_eq!(
+            split_command_token("user:a:b"),
+            (Some("user"), Cow::Borrowed("a:b"))
+        );
+        assert_eq!(escape_command_name("weird:name"), "weird\\:name");
+    }
+
+    #[test]
+    fn every_discovered_name_expands_to_its_own_file() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let mut files = vec![
+            "fix.md",
+            "Review/Security.md",
+            "review/perf.md",
+            "a_/b.md",
+            "a__/b.md",
+            "a/b/c.md",
+            "a/b__c.md",
+            "a__b/c.md",
+            "x__y/z.md",
+            "notes.v2.md",
+            "readme.md.md",
+            "deploy.prod.md",
+            "two words.md",
+            "-dash.md",
+        ];
+        // Windows does not allow `:` in file names.
+        if cfg!(unix) {
+            files.push("weird:name.md");
+            files.push("nested/a:b.md");
+        }
+        for relative in &files {
+            write_command(cwd.path(), relative, relative);
+            write_command(home.path(), relative, relative);
+        }
+
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+        let commands = discover::discover_commands(&roots);
+        assert!(commands.len() > files.len(), "{commands:?}");
+        for command in commands {
+            let input = format!("/{} trailing", command.full_name());
+            let expanded = expand_custom_command_in(&input, &roots).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(&command.path).unwrap(),
+                expanded.prompt,
+                "{input} should expand {}",
+                command.path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn underscore_runs_separate_on_their_last_two() {
+        for (name, segments) in [
+            ("review__security", vec!["review", "security"]),
+            ("snake_case", vec!["snake_case"]),
+            ("a___b", vec!["a_", "b"]),
+            ("a____b", vec!["a__", "b"]),
+            ("a__b___c", vec!["a", "b_", "c"]),
+            ("__a", vec!["", "a"]),
+            ("a__", vec!["a", ""]),
+        ] {
+            assert_eq!(name_segments(name), segments, "{name}");
+        }
+        assert!(!is_valid_command_name("__a"));
+        assert!(!is_valid_command_name("a__"));
+    }
+
+    #[test]
+    fn discovery_and_expansion_agree_on_underscore_runs() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "a_/b.md", "three");
+        write_command(cwd.path(), "a__/b.md", "four");
+        // A partial must not be reachable as `a` + `__` + `_b`.
+        write_command(cwd.path(), "a/_b.md", "partial");
+
+        assert_eq!(
+            discover_custom_commands_with_home(cwd.path(), None),
+            vec!["project:a____b".to_string(), "project:a___b".to_string()]
+        );
+        for (input, prompt) in [("/a___b", "three"), ("/a____b", "four")] {
+            let expanded = expand_custom_command_with_home(input, cwd.path(), None).unwrap();
+            assert_eq!(expanded.prompt, prompt, "{input}");
+        }
+    }
+
+    // Windows does not allow `:` in file names.
+    #[cfg(unix)]
+    #[test]
+    fn command_file_with_colon_round_trips() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "weird:name.md", "colon $1");
+
+        let commands = discover_custom_commands_with_home(cwd.path(), None);
+        assert_eq!(commands, vec!["project:weird\\:name".to_string()]);
+
+        let expanded =
+            expand_custom_command_with_home("/project:weird\\:name x", cwd.path(), None).unwrap();
+        assert_eq!(expanded.prompt, "colon x");
+
+        // Without the escape the second colon is part of the name too, but
+        // a bare name cannot contain an unescaped one.
+        let err = expand_custom_command_with_home("/weird:name", cwd.path(), None).unwrap_err();
+        assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "weird"));
+    }
+
+    #[test]
+    fn non_empty_home_is_used() {
+        assert_eq!(
+            home_from_env(Some(OsString::from("/home/me"))),
+            Some(PathBuf::from("/home/me"))
+        );
+        assert_eq!(home_from_env(None), None);
+    }
+}
+
+#[cfg(test)]
+mod test_support {
+    #![allow(clippy::unwrap_used)]
+
+    use super::PROJECT_COMMANDS_DIR;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    /// Create `<dir>/.codex/commands/<relative>` with `contents`, returning
+    /// its path. Used for both scopes, which share the directory layout.
+    pub(super) fn write_command(dir: &Path, relative: &str, contents: &str) -> PathBuf {
+        let path = dir.join(PROJECT_COMMANDS_DIR).join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
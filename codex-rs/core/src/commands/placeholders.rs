@@ -0,0 +1,545 @@
+//! Placeholder substitution, independent of the filesystem.
+
+use std::path::Path;
+
+use super::ExpandError;
+use super::ExpandOptions;
+use super::lint::placeholder_at;
+
+pub(super) const ARGUMENTS_PLACEHOLDER: &str = "$ARGUMENTS";
+/// Followed by an index `N`, e.g. `$ARGUMENTS_FROM_2`.
+pub(super) const ARGUMENTS_FROM_PLACEHOLDER: &str = "$ARGUMENTS_FROM_";
+pub(super) const SELECTION_PLACEHOLDER: &str = "$SELECTION";
+/// Expands to [`std::env::consts::OS`]: `linux`, `macos`, `windows`, ….
+const OS_PLACEHOLDER: &str = "$OS";
+
+/// Substitute the placeholders in `body` (see the [module docs](super)) for
+/// a command invoked with `args` from `cwd`. No files are read, so `body`
+/// must already have its frontmatter stripped and includes resolved.
+///
+/// `body` is scanned once and every value is inserted literally: a `$1` in
+/// an argument, the selection or an environment variable is never expanded
+/// itself.
+pub fn substitute_placeholders(
+    body: &str,
+    args: &str,
+    cwd: &Path,
+    opts: &ExpandOptions,
+) -> Result<String, ExpandError> {
+    let mut log = SubstitutionLog::default();
+    let out = substitute_tracked(body, args, cwd, opts, &mut log)?;
+    if opts.strict {
+        log.check()?;
+    }
+    Ok(out)
+}
+
+/// What a substitution did: how many placeholders it replaced and, without
+/// duplicates, the ones it could not resolve, for
+/// [`ExpandOptions::strict`].
+#[derive(Debug, Default)]
+pub(super) struct SubstitutionLog {
+    pub(super) substituted: usize,
+    unresolved: Vec<String>,
+}
+
+impl SubstitutionLog {
+    fn push_unresolved(&mut self, placeholder: &str) {
+        if !self.unresolved.iter().any(|seen| seen == placeholder) {
+            self.unresolved.push(placeholder.to_string());
+        }
+    }
+
+    pub(super) fn unresolved(&self) -> usize {
+        self.unresolved.len()
+    }
+
+    /// Fail with [`ExpandError::UnresolvedPlaceholders`] unless every
+    /// placeholder was resolved.
+    pub(super) fn check(self) -> Result<(), ExpandError> {
+        if self.unresolved.is_empty() {
+            Ok(())
+        } else {
+            Err(ExpandError::UnresolvedPlaceholders(self.unresolved))
+        }
+    }
+}
+
+/// Like [`substitute_placeholders`], recording what was substituted in `log`
+/// and adding the placeholders that could not be resolved to it instead of
+/// failing on them.
+pub(super) fn substitute_tracked(
+    body: &str,
+    args: &str,
+    cwd: &Path,
+    opts: &ExpandOptions,
+    log: &mut SubstitutionLog,
+) -> Result<String, ExpandError> {
+    let positional: Vec<&str> = args.split_whitespace().collect();
+    let cwd = cwd.to_string_lossy();
+    let named = [
+        (ARGUMENTS_PLACEHOLDER, args),
+        ("$INPUT", opts.input.as_deref().unwrap_or("")),
+        ("$CWD", &cwd),
+        (
+            SELECTION_PLACEHOLDER,
+            opts.selection.as_deref().unwrap_or(""),
+        ),
+        (OS_PLACEHOLDER, std::env::consts::OS),
+    ];
+
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let braced = match transformed_placeholder(rest, &named, &positional) {
+            Some(substitution) => Some(substitution),
+            None => env_placeholder(rest, opts)?,
+        };
+        if let Some(Substitution {
+            value,
+            len,
+            resolved,
+        }) = braced
+        {
+            if resolved {
+                log.substituted += 1;
+            } else {
+                log.push_unresolved(&rest[..len]);
+            }
+            out.push_str(&value);
+            rest = &rest[len..];
+        } else if let Some((index, len)) = arguments_from_placeholder(rest) {
+            match index {
+                Some(index) => {
+                    out.push_str(&positional.get(index - 1..).unwrap_or_default().join(" "));
+                    log.substituted += 1;
+                }
+                None => {
+                    log.push_unresolved(&rest[..len]);
+                    out.push_str(&rest[..len]);
+                }
+            }
+            rest = &rest[len..];
+        } else if let Some((placeholder, value)) = named.iter().find(|(placeholder, _)| {
+            // `$OS` is short enough to start other names, e.g. `$OSTYPE` in
+            // a shell snippet, so it must not be followed by one.
+            rest.strip_prefix(placeholder).is_some_and(|after| {
+                *placeholder != OS_PLACEHOLDER
+                    || !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            })
+        }) {
+            out.push_str(value);
+            log.substituted += 1;
+            rest = &rest[placeholder.len()..];
+        } else if let Some((index, len)) = positional_placeholder(rest) {
+            match positional.get(index - 1) {
+                Some(value) => {
+                    out.push_str(value);
+                    log.substituted += 1;
+                }
+                None => log.push_unresolved(&rest[..len]),
+            }
+            rest = &rest[len..];
+        } else {
+            if let Some(placeholder) = placeholder_at(rest) {
+                log.push_unresolved(placeholder);
+            }
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The index and length of a `$1`, `$2`, … at the start of `text`. The index
+/// is every digit following the `$`, so `$10` is the tenth argument rather
+/// than `$1` followed by `0`. A placeholder may be directly surrounded by
+/// other text (`pre-$1-post`). `$0` is not a placeholder.
+fn positional_placeholder(text: &str) -> Option<(usize, usize)> {
+    let (index, len) = leading_index(&text[1..])?;
+    Some((index, 1 + len))
+}
+
+/// The index and length of a `$ARGUMENTS_FROM_N` at the start of `text`. It
+/// expands to the `N`th argument and every one after it, joined with single
+/// spaces, so `$ARGUMENTS_FROM_1` is every argument and an `N` past the last
+/// argument is the empty string. The index is every digit following the
+/// prefix, like a positional one; it is `None` for an index of zero, e.g.
+/// `$ARGUMENTS_FROM_0`, which is left as written.
+fn arguments_from_placeholder(text: &str) -> Option<(Option<usize>, usize)> {
+    let digits = text.strip_prefix(ARGUMENTS_FROM_PLACEHOLDER)?;
+    let len = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    if len == 0 {
+        return None;
+    }
+    let index = leading_index(&digits[..len]).map(|(index, _)| index);
+    Some((index, ARGUMENTS_FROM_PLACEHOLDER.len() + len))
+}
+
+/// The value and length of the non-zero number that `text` starts with.
+pub(super) fn leading_index(text: &str) -> Option<(usize, usize)> {
+    let len = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let index = text[..len].parse::<usize>().ok()?;
+    (index != 0).then_some((index, len))
+}
+
+/// What a `${…}` placeholder at the start of some text expands to.
+struct Substitution {
+    value: String,
+    /// Length of the placeholder.
+    len: usize,
+    /// `false` when the placeholder names a missing argument or an unset
+    /// variable and `value` stands in for it.
+    resolved: bool,
+}
+
+/// The value and length of a `${NAME:transform}` at the start of `text`,
+/// where `NAME` is a positional index or a named placeholder without its `$`
+/// (e.g. `${1:shellquote}`, `${ARGUMENTS:shellquote}`). The only transform
+/// is `shellquote`, which quotes the value as a single POSIX shell word.
+/// Anything else is not a transformed placeholder.
+fn transformed_placeholder(
+    text: &str,
+    named: &[(&str, &str)],
+    positional: &[&str],
+) -> Option<Substitution> {
+    let after = text.strip_prefix("${")?;
+    let end = after.find('}')?;
+    let (name, transform) = after[..end].split_once(':')?;
+    let value = match leading_index(name) {
+        Some((index, len)) if len == name.len() => positional.get(index - 1).copied(),
+        _ => Some(
+            named
+                .iter()
+                .find(|(placeholder, _)| placeholder.strip_prefix('$') == Some(name))?
+                .1,
+        ),
+    };
+    let quote = match transform {
+        "shellquote" => shell_quote,
+        _ => return None,
+    };
+    // A missing argument is quoted like an empty one.
+    Some(Substitution {
+        value: quote(value.unwrap_or("")),
+        len: end + 3,
+        resolved: value.is_some(),
+    })
+}
+
+/// `value` as a single word for a POSIX shell: unchanged when it consists
+/// only of characters no shell treats specially, otherwise in single quotes
+/// with each `'` written as `'\''`.
+fn shell_quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// The value and length of a `${NAME}` at the start of `text`: the entry
+/// `NAME` of [`ExpandOptions::vars`], else the environment variable `NAME`,
+/// or the empty string when neither is set (an error with `strict_env`).
+/// Names consist of ASCII letters, digits and `_` and do not start with a
+/// digit; anything else after `${` is not a placeholder.
+fn env_placeholder(text: &str, opts: &ExpandOptions) -> Result<Option<Substitution>, ExpandError> {
+    let Some(after) = text.strip_prefix("${") else {
+        return Ok(None);
+    };
+    let Some(name) = after.find('}').map(|end| &after[..end]) else {
+        return Ok(None);
+    };
+    if !is_env_var_name(name) {
+        return Ok(None);
+    }
+    let value = match opts.vars.get(name) {
+        Some(value) => Some(value.clone()),
+        None => std::env::var(name).ok(),
+    };
+    if value.is_none() && opts.strict_env {
+        return Err(ExpandError::UndefinedEnvVar(name.to_string()));
+    }
+    Ok(Some(Substitution {
+        resolved: value.is_some(),
+        value: value.unwrap_or_default(),
+        len: name.len() + 3,
+    }))
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn substitute(body: &str, args: &str, opts: &ExpandOptions) -> String {
+        substitute_placeholders(body, args, Path::new("/work/repo"), opts).unwrap()
+    }
+
+    #[test]
+    fn substitutes_every_placeholder_type() {
+        let opts = ExpandOptions {
+            input: Some("/project:fix a b".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            substitute("[$ARGUMENTS] [$INPUT] [$CWD] [$1] [$2] [$3]", "a b", &opts),
+            "[a b] [/project:fix a b] [/work/repo] [a] [b] []"
+        );
+    }
+
+    #[test]
+    fn os_expands_to_the_build_targets_os() {
+        let os = std::env::consts::OS;
+        assert_eq!(
+            substitute(
+                "on $OS, run $1; $OSTYPE $OS_X",
+                "make",
+                &ExpandOptions::default()
+            ),
+            format!("on {os}, run make; $OSTYPE $OS_X")
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(os, "linux");
+        #[cfg(target_os = "macos")]
+        assert_eq!(os, "macos");
+        #[cfg(target_os = "windows")]
+        assert_eq!(os, "windows");
+    }
+
+    #[test]
+    fn shellquote_quotes_values_as_one_shell_word() {
+        let body = "grep ${1:shellquote} ${ARGUMENTS:shellquote} ${4:shellquote}";
+        assert_eq!(
+            substitute(body, "it's \"a b\"", &ExpandOptions::default()),
+            r#"grep 'it'\''s' 'it'\''s "a b"' ''"#
+        );
+        assert_eq!(
+            substitute(
+                "run ${1:shellquote}",
+                "src/main.rs",
+                &ExpandOptions::default()
+            ),
+            "run src/main.rs"
+        );
+        assert_eq!(
+            substitute(
+                "x ${1:upper} ${ARGUMENTS:}",
+                "a;rm",
+                &ExpandOptions::default()
+            ),
+            "x ${1:upper} ${ARGUMENTS:}"
+        );
+    }
+
+    #[test]
+    fn arguments_from_an_index_join_the_rest() {
+        let body = "[$1] [$ARGUMENTS_FROM_2] [$ARGUMENTS_FROM_1] [$ARGUMENTS_FROM_4]";
+        assert_eq!(
+            substitute(body, "review  the\tparser", &ExpandOptions::default()),
+            "[review] [the parser] [review the parser] []"
+        );
+        assert_eq!(
+            substitute(
+                "[$ARGUMENTS_FROM_2x] [$ARGUMENTS_FROM_0] [$ARGUMENTS_FROM_00]",
+                "a b",
+                &ExpandOptions::default()
+            ),
+            "[bx] [$ARGUMENTS_FROM_0] [$ARGUMENTS_FROM_00]"
+        );
+        let strict = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = substitute_placeholders("$ARGUMENTS_FROM_0", "a b", Path::new("/"), &strict)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unresolved placeholders: $ARGUMENTS_FROM_0"
+        );
+    }
+
+    #[test]
+    fn input_and_selection_are_empty_when_not_provided() {
+        assert_eq!(
+            substitute("[$INPUT] [$SELECTION] $1", "x", &ExpandOptions::default()),
+            "[] [] x"
+        );
+    }
+
+    #[test]
+    fn selection_is_substituted_when_provided() {
+        let opts = ExpandOptions {
+            selection: Some("fn main() {}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            substitute("Refactor this: $SELECTION", "", &opts),
+            "Refactor this: fn main() {}"
+        );
+    }
+
+    #[test]
+    fn session_vars_shadow_environment_variables() {
+        let opts = ExpandOptions {
+            vars: HashMap::from([
+                ("PATH".to_string(), "session path".to_string()),
+                ("TICKET".to_string(), "ABC-1 $1".to_string()),
+            ]),
+            strict_env: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            substitute("[${PATH}] [${TICKET}] [$TICKET]", "x", &opts),
+            "[session path] [ABC-1 $1] [$TICKET]"
+        );
+    }
+
+    #[test]
+    fn env_placeholders_expand_and_are_strict_on_request() {
+        let path = std::env::var("PATH").unwrap();
+        let body = "[${PATH}] [${CODEX_TEST_UNSET_VAR}] [${1}] [${unterminated]";
+        assert_eq!(
+            substitute(body, "", &ExpandOptions::default()),
+            format!("[{path}] [] [${{1}}] [${{unterminated]")
+        );
+
+        let strict = ExpandOptions {
+            strict_env: true,
+            ..Default::default()
+        };
+        let err = substitute_placeholders(body, "", Path::new("/"), &strict).unwrap_err();
+        assert!(
+            matches!(&err, ExpandError::UndefinedEnvVar(name) if name == "CODEX_TEST_UNSET_VAR"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn strict_mode_fails_on_unresolved_placeholders_only() {
+        let strict = ExpandOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let body = "$1 $2 ${2:shellquote} $ARGUMENTS $CWD $0 $ARGUMENTZ $2 $price";
+        let err = substitute_placeholders(body, "one", Path::new("/"), &strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unresolved placeholders: $2, ${2:shellquote}, $0, $ARGUMENTZ"
+        );
+
+        assert_eq!(
+            substitute(
+                "$1 ${1:shellquote} $ARGUMENTS_FROM_3 cost $5x",
+                "a b c d e",
+                &strict
+            ),
+            "a a c d e cost ex"
+        );
+    }
+
+    #[test]
+    fn positional_placeholders_concatenate_with_surrounding_text() {
+        let opts = ExpandOptions::default();
+        assert_eq!(
+            substitute("prefix-$1-suffix", "v1", &opts),
+            "prefix-v1-suffix"
+        );
+        assert_eq!(substitute("v$1beta", "2", &opts), "v2beta");
+    }
+
+    #[test]
+    fn multi_digit_positional_placeholders_use_every_digit() {
+        let args = "a1 a2 a3 a4 a5 a6 a7 a8 a9 a10 a11 a12";
+        let opts = ExpandOptions::default();
+        assert_eq!(
+            substitute("[$1] [$10] [$11] [$12] [$13] [$0]", args, &opts),
+            "[a1] [a10] [a11] [a12] [] [$0]"
+        );
+        assert_eq!(substitute("$10x", args, &opts), "a10x");
+    }
+
+    #[test]
+    fn values_are_inserted_literally() {
+        let opts = ExpandOptions {
+            input: Some("/echo $ARGUMENTS $1".to_string()),
+            selection: Some("${PATH} $CWD".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            substitute(
+                "echo $ARGUMENTS|$1|$2|$INPUT|$SELECTION",
+                "$ARGUMENTS $1",
+                &opts
+            ),
+            "echo $ARGUMENTS $1|$ARGUMENTS|$1|/echo $ARGUMENTS $1|${PATH} $CWD"
+        );
+    }
+
+    #[test]
+    fn matches_replacing_each_placeholder_in_turn() {
+        // Replacing one placeholder after another, as substitution used to
+        // (and as `benches/substitution.rs` does for comparison), agrees with
+        // the single pass whenever no value contains a `$` or needs quoting.
+        fn replace_each(body: &str, args: &str, opts: &ExpandOptions) -> String {
+            let positional: Vec<&str> = args.split_whitespace().collect();
+            let mut out = body.to_string();
+            for (index, arg) in positional.iter().enumerate().rev() {
+                let n = index + 1;
+                out = out
+                    .replace(&format!("${{{n}:shellquote}}"), arg)
+                    .replace(
+                        &format!("$ARGUMENTS_FROM_{n}"),
+                        &positional[index..].join(" "),
+                    )
+                    .replace(&format!("${n}"), arg);
+            }
+            out.replace("${ARGUMENTS:shellquote}", args)
+                .replace(ARGUMENTS_PLACEHOLDER, args)
+                .replace("$INPUT", opts.input.as_deref().unwrap_or(""))
+                .replace("$CWD", "/work/repo")
+                .replace(
+                    SELECTION_PLACEHOLDER,
+                    opts.selection.as_deref().unwrap_or(""),
+                )
+                .replace(OS_PLACEHOLDER, std::env::consts::OS)
+                .replace("${CODEX_TEST_UNSET_VARIABLE}", "")
+        }
+
+        let args = "src/lib.rs the failing tests";
+        let opts = ExpandOptions {
+            input: Some(format!("/project:fix {args}")),
+            selection: Some("let x = 1;".to_string()),
+            ..Default::default()
+        };
+        let line = "Fix $1 in $CWD ($ARGUMENTS_FROM_2) for $INPUT: ${2:shellquote} $ARGUMENTS \
+                    ${CODEX_TEST_UNSET_VARIABLE} $SELECTION $OS $$ costs $ 5 $4$4 $PATH\n";
+        let body = line.repeat(10_000);
+        assert_eq!(
+            substitute(&body, args, &opts),
+            replace_each(&body, args, &opts)
+        );
+    }
+
+    #[test]
+    fn text_that_is_not_a_placeholder_is_unchanged() {
+        let body = "Costs $ 5; see $PATH and $arguments.";
+        assert_eq!(substitute(body, "x", &ExpandOptions::default()), body);
+    }
+}
@@ -0,0 +1,132 @@
+//! Transforms applied to a command's expanded prompt, named by the
+//! `postprocess` frontmatter key, e.g. `postprocess: [dedent, trim]`.
+
+/// A transform of an expanded prompt. Transforms are applied in the order
+/// they are listed, after every include, `@run` and placeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostProcess {
+    /// `trim`: remove leading and trailing whitespace, including blank lines.
+    Trim,
+    /// `dedent`: remove the leading whitespace that every non-blank line
+    /// shares, e.g. from a template indented to line up in its file. Lines
+    /// that are only whitespace become empty.
+    Dedent,
+    /// `squeeze-blank-lines`: replace each run of blank lines with a single
+    /// empty line.
+    SqueezeBlankLines,
+}
+
+impl PostProcess {
+    /// The transform written `name` in frontmatter.
+    pub(super) fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "trim" => Ok(Self::Trim),
+            "dedent" => Ok(Self::Dedent),
+            "squeeze-blank-lines" => Ok(Self::SqueezeBlankLines),
+            _ => Err(format!(
+                "unknown postprocess `{name}`: expected trim, dedent or squeeze-blank-lines"
+            )),
+        }
+    }
+
+    pub(super) fn apply(self, prompt: &str) -> String {
+        match self {
+            Self::Trim => prompt.trim().to_string(),
+            Self::Dedent => dedent(prompt),
+            Self::SqueezeBlankLines => squeeze_blank_lines(prompt),
+        }
+    }
+}
+
+/// The transforms listed in a `postprocess` value.
+pub(super) fn parse_postprocess(names: &[String]) -> Result<Vec<PostProcess>, String> {
+    names
+        .iter()
+        .map(|name| PostProcess::from_name(name))
+        .collect()
+}
+
+fn dedent(prompt: &str) -> String {
+    fn indent(line: &str) -> &str {
+        let len = line.len() - line.trim_start_matches([' ', '\t']).len();
+        &line[..len]
+    }
+
+    let common = prompt
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(indent)
+        .reduce(|common, indent| {
+            let len = common
+                .bytes()
+                .zip(indent.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        })
+        .unwrap_or("");
+    prompt
+        .split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                &line[line.trim_end_matches(['\r', '\n']).len()..]
+            } else {
+                &line[common.len()..]
+            }
+        })
+        .collect()
+}
+
+fn squeeze_blank_lines(prompt: &str) -> String {
+    let mut out = String::with_capacity(prompt.len());
+    let mut previous_blank = false;
+    for line in prompt.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if blank && previous_blank {
+            continue;
+        }
+        previous_blank = blank;
+        if blank {
+            out.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+        } else {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dedent_removes_the_shared_indentation() {
+        assert_eq!(
+            PostProcess::Dedent.apply("    Review:\n      - $1\n   \n    Thanks\n"),
+            "Review:\n  - $1\n\nThanks\n"
+        );
+        assert_eq!(
+            PostProcess::Dedent.apply("\tTabbed\n  Spaced\n"),
+            "\tTabbed\n  Spaced\n"
+        );
+    }
+
+    #[test]
+    fn squeeze_collapses_runs_of_blank_lines() {
+        assert_eq!(
+            PostProcess::SqueezeBlankLines.apply("One\n\n  \n\nTwo\n\nThree\n\n\n"),
+            "One\n\nTwo\n\nThree\n\n"
+        );
+    }
+
+    #[test]
+    fn names_parse_in_order() {
+        let names = ["squeeze-blank-lines".to_string(), "trim".to_string()];
+        assert_eq!(
+            parse_postprocess(&names),
+            Ok(vec![PostProcess::SqueezeBlankLines, PostProcess::Trim])
+        );
+        assert!(parse_postprocess(&["upper".to_string()]).is_err());
+    }
+}
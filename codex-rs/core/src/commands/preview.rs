@@ -0,0 +1,179 @@
+//! Describing what an invocation would expand, without expanding it, e.g.
+//! for an `/explain /project:fix` preview, and expanding a command with
+//! sample arguments while iterating on it.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::CommandRoots;
+use super::ExpandError;
+use super::Frontmatter;
+use super::Scope;
+use super::expand::expand_in;
+use super::expand::parse_invocation;
+use super::expand::resolve_command;
+use super::frontmatter::split_frontmatter;
+use super::lint::placeholders;
+
+/// What [`explain_command`] found for an invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPreview {
+    pub scope: Scope,
+    /// The command name as typed, with `\:` escapes resolved.
+    pub name: String,
+    /// The command file the invocation resolves to.
+    pub path: PathBuf,
+    /// The file's frontmatter metadata.
+    pub frontmatter: Frontmatter,
+    /// Each distinct placeholder in the prompt (the `template`, or else the
+    /// body), including the `$`, in order of first appearance, e.g.
+    /// `["$ARGUMENTS", "$1"]`. Placeholders in included partials are not
+    /// listed, and `raw` commands have none.
+    pub placeholders: Vec<String>,
+}
+
+/// Resolve `input` (e.g. `/project:fix`) to its command file and report the
+/// file, its frontmatter and its placeholders. Arguments are optional;
+/// `--env=` and `default_args` select the file as they would for expansion,
+/// but nothing is substituted.
+pub fn explain_command(input: &str, roots: &CommandRoots) -> Result<CommandPreview, ExpandError> {
+    let invocation = parse_invocation(input).ok_or(ExpandError::NotACommand)?;
+    let scope = invocation.resolve_scope(roots)?;
+    let (found, _) = resolve_command(scope, &invocation.name, invocation.args, roots, None)?;
+
+    let (frontmatter, body) = split_frontmatter(&found.contents);
+    // A raw command's prompt has no placeholders to scan.
+    let prompt = match frontmatter.raw {
+        Some(true) => "",
+        _ => frontmatter.template.as_deref().unwrap_or(body),
+    };
+    let mut found_placeholders: Vec<String> = Vec::new();
+    for line in prompt.lines() {
+        for (_, placeholder) in placeholders(line) {
+            if !found_placeholders.iter().any(|seen| seen == placeholder) {
+                found_placeholders.push(placeholder.to_string());
+            }
+        }
+    }
+
+    Ok(CommandPreview {
+        scope,
+        name: invocation.name.into_owned(),
+        path: found.path,
+        frontmatter,
+        placeholders: found_placeholders,
+    })
+}
+
+/// Expand the command `name` (e.g. `project:fix` or `/fix`) with
+/// `sample_args` and return the prompt, for an author trying a command out.
+/// No session state is used: the expansion gets the default
+/// [`ExpandOptions`](super::ExpandOptions), so there is no selection and
+/// there are no session variables. The user scope is resolved against
+/// `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn preview(name: &str, sample_args: &str, cwd: &Path) -> Result<String, ExpandError> {
+    preview_in(name, sample_args, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`preview`], but looking the command up in `roots`.
+pub fn preview_in(
+    name: &str,
+    sample_args: &str,
+    roots: &CommandRoots,
+) -> Result<String, ExpandError> {
+    let input = format!("/{}", name.trim().strip_prefix('/').unwrap_or(name.trim()));
+    let invocation = parse_invocation(&input).ok_or(ExpandError::NotACommand)?;
+    if !invocation.args.is_empty() {
+        return Err(ExpandError::InvalidName(name.to_string()));
+    }
+    let expanded = expand_in(
+        invocation.resolve_scope(roots)?,
+        &invocation.name,
+        sample_args,
+        roots,
+    )?;
+    Ok(expanded.prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn reports_path_placeholders_and_frontmatter() {
+        let cwd = TempDir::new().unwrap();
+        let path = write_command(
+            cwd.path(),
+            "review/security.md",
+            "---\nnext: project:fix\nstrict_env: true\n---\nReview $1: $ARGUMENTS, $1.\n$CWD\n",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        assert_eq!(
+            explain_command("/project:review__security", &roots).unwrap(),
+            CommandPreview {
+                scope: Scope::Project,
+                name: "review__security".to_string(),
+                path,
+                frontmatter: Frontmatter {
+                    next: Some("project:fix".to_string()),
+                    strict_env: Some(true),
+                    ..Default::default()
+                },
+                placeholders: vec![
+                    "$1".to_string(),
+                    "$ARGUMENTS".to_string(),
+                    "$CWD".to_string()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn selects_the_same_variant_as_expansion() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "deploy.md", "Deploy $ARGUMENTS");
+        let prod = write_command(
+            cwd.path(),
+            "deploy.prod.md",
+            "---\ntemplate: Carefully deploy $1\n---\nNotes with $INPUT",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        let preview = explain_command("/deploy --env=prod api", &roots).unwrap();
+        assert_eq!(preview.path, prod);
+        assert_eq!(preview.placeholders, vec!["$1".to_string()]);
+
+        let err = explain_command("/missing", &roots).unwrap_err();
+        assert!(matches!(err, ExpandError::FileNotFound(_)), "{err}");
+    }
+
+    #[test]
+    fn previews_a_command_with_sample_arguments() {
+        let cwd = TempDir::new().unwrap();
+        write_command(
+            cwd.path(),
+            "fix.md",
+            "---\ndefault_args: --quick\n---\nFix $1 [$ARGUMENTS] ${UNSET_PREVIEW_VAR}$SELECTION",
+        );
+        let roots = CommandRoots::new(cwd.path(), None);
+
+        assert_eq!(
+            preview_in("project:fix", "tests now", &roots).unwrap(),
+            "Fix --quick [--quick tests now] "
+        );
+        assert_eq!(
+            preview_in("/fix", "", &roots).unwrap(),
+            "Fix --quick [--quick] "
+        );
+        assert!(matches!(
+            preview_in("fix extra", "", &roots),
+            Err(ExpandError::InvalidName(_))
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+//! Turning a free-form title into a command name, e.g. when a user creates a
+//! command interactively.
+
+use super::COMMAND_FILE_EXTENSION;
+use super::MAX_NAME_COMPONENT_BYTES;
+
+/// Separator between the words of a sanitized name.
+const WORD_SEPARATOR: char = '-';
+
+/// The command name for `title`, e.g. `fix-the-issue` for `Fix the Issue!`.
+/// Letters and digits (in any script) are lowercased and kept, apostrophes
+/// are dropped, and every other run of characters becomes a single `-`,
+/// with none at either end. The name is cut short, at a word boundary where
+/// possible, to fit in a file name alongside `.md`.
+///
+/// The result is valid both as a file name and as a command name, or empty
+/// when `title` has no letters or digits; the caller must then ask for
+/// another title.
+pub fn sanitize_command_name(title: &str) -> String {
+    let max_len = MAX_NAME_COMPONENT_BYTES - COMMAND_FILE_EXTENSION.len() - 1;
+    let mut name = String::with_capacity(title.len().min(max_len));
+    for c in title.chars() {
+        if matches!(c, '\'' | '\u{2019}') {
+            continue;
+        }
+        let start = name.len();
+        if c.is_alphanumeric() {
+            name.extend(c.to_lowercase());
+        } else if !name.is_empty() && !name.ends_with(WORD_SEPARATOR) {
+            name.push(WORD_SEPARATOR);
+        }
+        if name.len() > max_len {
+            name.truncate(start);
+            if let Some(boundary) = name.rfind(WORD_SEPARATOR) {
+                name.truncate(boundary);
+            }
+            break;
+        }
+    }
+    let len = name.trim_end_matches(WORD_SEPARATOR).len();
+    name.truncate(len);
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn titles_become_lowercase_hyphenated_names() {
+        for (title, name) in [
+            ("Fix the Issue!", "fix-the-issue"),
+            ("  Review   PR #42 ", "review-pr-42"),
+            ("Don't panic", "dont-panic"),
+            ("src/main.rs: explain", "src-main-rs-explain"),
+            ("..hidden__partial", "hidden-partial"),
+            ("Café Über Straße", "café-über-straße"),
+            ("日本語 レビュー", "日本語-レビュー"),
+            ("🚀 Launch 🚀", "launch"),
+            ("?!", ""),
+        ] {
+            assert_eq!(sanitize_command_name(title), name, "{title:?}");
+        }
+    }
+
+    #[test]
+    fn long_titles_are_cut_at_a_word_boundary() {
+        let name = sanitize_command_name(&"word ".repeat(100));
+        assert!(name.len() <= MAX_NAME_COMPONENT_BYTES - ".md".len());
+        assert!(name.ends_with("word"), "{name}");
+    }
+}
@@ -0,0 +1,304 @@
+//! Argument schemas declared with the `args` frontmatter key.
+//!
+//! A schema lists the named arguments a command accepts, as YAML flow
+//! mappings or TOML inline tables:
+//!
+//! ```text
+//! ---
+//! args: [{name: env, type: enum, values: [dev, prod]}, {name: retries, type: integer}]
+//! ---
+//! ```
+//!
+//! A named argument is passed as `--name=value`. Its `type` is `string` (the
+//! default), `integer` or `enum`, which requires `values`. Arguments the
+//! schema does not mention, and declared ones that are not passed, are
+//! accepted.
+
+use serde::Deserialize;
+
+use super::ExpandError;
+
+/// A named argument declared in a command's `args` frontmatter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgSpec {
+    /// Name of the `--name=value` option.
+    pub name: String,
+    pub kind: ArgType,
+}
+
+/// The values an [`ArgSpec`] accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgType {
+    /// Any value.
+    String,
+    /// A decimal integer, optionally signed.
+    Integer,
+    /// One of the listed values, compared exactly.
+    Enum(Vec<String>),
+}
+
+impl ArgSpec {
+    /// Build a spec from the keys of one schema entry.
+    fn from_parts(
+        name: Option<String>,
+        kind: Option<String>,
+        values: Option<Vec<String>>,
+    ) -> Result<Self, String> {
+        let name = name
+            .filter(|name| !name.is_empty())
+            .ok_or("argument is missing `name`")?;
+        let kind = match (kind.as_deref().unwrap_or("string"), values) {
+            ("enum", Some(values)) if !values.is_empty() => ArgType::Enum(values),
+            ("enum", _) => return Err(format!("enum argument `{name}` has no `values`")),
+            (_, Some(_)) => {
+                return Err(format!(
+                    "only enum arguments can have `values`, not `{name}`"
+                ));
+            }
+            ("string", None) => ArgType::String,
+            ("integer", None) => ArgType::Integer,
+            (kind, None) => return Err(format!("unknown type `{kind}` for argument `{name}`")),
+        };
+        Ok(Self { name, kind })
+    }
+
+    /// Whether `value` is acceptable, or else a description of what is.
+    fn check(&self, value: &str) -> Result<(), String> {
+        match &self.kind {
+            ArgType::String => Ok(()),
+            ArgType::Integer if value.parse::<i64>().is_ok() => Ok(()),
+            ArgType::Integer => Err("an integer".to_string()),
+            ArgType::Enum(values) if values.iter().any(|allowed| allowed == value) => Ok(()),
+            ArgType::Enum(values) => Err(format!("one of {}", values.join(", "))),
+        }
+    }
+}
+
+/// One entry of a `+++` frontmatter `args` array.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ArgSpecToml {
+    name: Option<String>,
+    r#type: Option<String>,
+    values: Option<Vec<String>>,
+}
+
+/// The schema written in TOML.
+pub(super) fn schema_from_toml(entries: Vec<ArgSpecToml>) -> Result<Vec<ArgSpec>, String> {
+    entries
+        .into_iter()
+        .map(|entry| ArgSpec::from_parts(entry.name, entry.r#type, entry.values))
+        .collect()
+}
+
+/// The schema written as a YAML flow sequence, e.g. `[{name: env}]`.
+pub(super) fn parse_yaml_schema(value: &str) -> Result<Vec<ArgSpec>, String> {
+    let mut parser = FlowParser { rest: value };
+    let schema = parser.value()?;
+    if !parser.rest.trim().is_empty() {
+        return Err(format!("unexpected `{}` after `args`", parser.rest.trim()));
+    }
+    let Flow::List(entries) = schema else {
+        return Err("`args` must be a list".to_string());
+    };
+    entries
+        .into_iter()
+        .map(|entry| {
+            let Flow::Map(keys) = entry else {
+                return Err("each argument must be a `{name: …}` mapping".to_string());
+            };
+            let (mut name, mut kind, mut values) = (None, None, None);
+            for (key, value) in keys {
+                match (key.as_str(), value) {
+                    ("name", Flow::Scalar(value)) => name = Some(value),
+                    ("type", Flow::Scalar(value)) => kind = Some(value),
+                    ("values", Flow::List(items)) => {
+                        values = Some(
+                            items
+                                .into_iter()
+                                .map(|item| match item {
+                                    Flow::Scalar(item) => Ok(item),
+                                    _ => Err("`values` must be a list of strings".to_string()),
+                                })
+                                .collect::<Result<_, _>>()?,
+                        );
+                    }
+                    (key @ ("name" | "type" | "values"), _) => {
+                        return Err(format!("invalid `{key}` in argument"));
+                    }
+                    (key, _) => return Err(format!("unknown key `{key}` in argument")),
+                }
+            }
+            ArgSpec::from_parts(name, kind, values)
+        })
+        .collect()
+}
+
+/// Check the `--name=value` options in `args` against `schema`.
+pub(super) fn validate_args(schema: &[ArgSpec], args: &str) -> Result<(), ExpandError> {
+    for (name, value) in args
+        .split_whitespace()
+        .filter_map(|token| token.strip_prefix("--")?.split_once('='))
+    {
+        let Some(spec) = schema.iter().find(|spec| spec.name == name) else {
+            continue;
+        };
+        spec.check(value).map_err(|expected| {
+            ExpandError::InvalidArgument(name.to_string(), value.to_string(), expected)
+        })?;
+    }
+    Ok(())
+}
+
+/// A value in YAML flow syntax.
+enum Flow {
+    Scalar(String),
+    List(Vec<Flow>),
+    Map(Vec<(String, Flow)>),
+}
+
+/// Parser for the subset of YAML flow syntax schemas use: `[…]` sequences,
+/// `{key: value}` mappings and plain or quoted scalars.
+struct FlowParser<'a> {
+    rest: &'a str,
+}
+
+impl FlowParser<'_> {
+    fn value(&mut self) -> Result<Flow, String> {
+        self.rest = self.rest.trim_start();
+        if let Some(rest) = self.rest.strip_prefix('[') {
+            self.rest = rest;
+            let mut items = Vec::new();
+            while !self.close(']')? {
+                items.push(self.value()?);
+                self.separator(']')?;
+            }
+            Ok(Flow::List(items))
+        } else if let Some(rest) = self.rest.strip_prefix('{') {
+            self.rest = rest;
+            let mut keys = Vec::new();
+            while !self.close('}')? {
+                let key = self.scalar(&[':'])?;
+                self.rest = self
+                    .rest
+                    .trim_start()
+                    .strip_prefix(':')
+                    .ok_or_else(|| format!("expected `:` after `{key}`"))?;
+                keys.push((key, self.value()?));
+                self.separator('}')?;
+            }
+            Ok(Flow::Map(keys))
+        } else {
+            self.scalar(&[',', ']', '}']).map(Flow::Scalar)
+        }
+    }
+
+    /// Consume `close` if it comes next.
+    fn close(&mut self, close: char) -> Result<bool, String> {
+        self.rest = self.rest.trim_start();
+        if self.rest.is_empty() {
+            return Err(format!("missing `{close}`"));
+        }
+        match self.rest.strip_prefix(close) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Consume the `,` after an item, unless `close` comes next.
+    fn separator(&mut self, close: char) -> Result<(), String> {
+        self.rest = self.rest.trim_start();
+        if let Some(rest) = self.rest.strip_prefix(',') {
+            self.rest = rest;
+            Ok(())
+        } else if self.rest.starts_with(close) {
+            Ok(())
+        } else if self.rest.is_empty() {
+            Err(format!("missing `{close}`"))
+        } else {
+            Err(format!("expected `,` or `{close}`"))
+        }
+    }
+
+    /// A quoted scalar, or a plain one ending before any of `terminators`.
+    fn scalar(&mut self, terminators: &[char]) -> Result<String, String> {
+        self.rest = self.rest.trim_start();
+        if let Some(quote) = self.rest.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+            let (scalar, rest) = self.rest[1..]
+                .split_once(quote)
+                .ok_or_else(|| format!("unterminated {quote}"))?;
+            self.rest = rest;
+            return Ok(scalar.to_string());
+        }
+        let end = self
+            .rest
+            .find(|c| terminators.contains(&c))
+            .unwrap_or(self.rest.len());
+        let scalar = self.rest[..end].trim();
+        if scalar.is_empty() || scalar.contains(['[', '{']) {
+            return Err(format!("expected a value, found `{}`", self.rest.trim()));
+        }
+        self.rest = &self.rest[end..];
+        Ok(scalar.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn yaml_schemas_parse_into_specs() {
+        assert_eq!(
+            parse_yaml_schema("[{name: env, type: enum, values: [dev, 'prod']}, {name: note}]"),
+            Ok(vec![
+                ArgSpec {
+                    name: "env".to_string(),
+                    kind: ArgType::Enum(vec!["dev".to_string(), "prod".to_string()]),
+                },
+                ArgSpec {
+                    name: "note".to_string(),
+                    kind: ArgType::String,
+                },
+            ])
+        );
+        assert_eq!(
+            parse_yaml_schema("[{name: n, type: float}]"),
+            Err("unknown type `float` for argument `n`".to_string())
+        );
+        assert_eq!(
+            parse_yaml_schema("[{name: env, type: enum}]"),
+            Err("enum argument `env` has no `values`".to_string())
+        );
+        assert_eq!(
+            parse_yaml_schema("[{name: env"),
+            Err("missing `}`".to_string())
+        );
+    }
+
+    #[test]
+    fn named_arguments_are_checked_against_the_schema() {
+        let schema = parse_yaml_schema(
+            "[{name: env, type: enum, values: [dev, prod]}, {name: retries, type: integer}]",
+        )
+        .unwrap();
+
+        assert!(validate_args(&schema, "--env=prod --retries=-3 --other=x free text").is_ok());
+        assert!(validate_args(&schema, "").is_ok());
+        let err = validate_args(&schema, "--env=staging").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value `staging` for argument `env`: expected one of dev, prod"
+        );
+        assert!(matches!(
+            validate_args(&schema, "--retries=many"),
+            Err(ExpandError::InvalidArgument(name, _, _)) if name == "retries"
+        ));
+    }
+}
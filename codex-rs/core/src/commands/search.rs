@@ -0,0 +1,172 @@
+//! Searching command names and bodies.
+
+use std::path::Path;
+
+use super::CommandCache;
+use super::CommandRoots;
+use super::DiscoveredCommand;
+use super::MAX_COMMAND_FILE_BYTES;
+use super::command_key;
+use super::discover::discover_commands;
+use super::read_command_file;
+
+/// Search the commands available from `cwd` for `query`, matched
+/// case-insensitively as a substring of the command name or body.
+///
+/// Results are ranked: exact name matches first, then name prefixes, then
+/// other name matches, then commands that only match in their body. Within a
+/// rank, discovery order is preserved. A blank query returns every command.
+/// The user scope is resolved against `$HOME` and `CODEX_COMMANDS_PATH`.
+pub fn search_commands(query: &str, cwd: &Path) -> Vec<DiscoveredCommand> {
+    search_commands_in(query, &CommandRoots::for_cwd(cwd))
+}
+
+/// Like [`search_commands`], but with an explicit home directory for the user
+/// scope (`None` disables it).
+pub fn search_commands_with_home(
+    query: &str,
+    cwd: &Path,
+    home: Option<&Path>,
+) -> Vec<DiscoveredCommand> {
+    search_commands_in(query, &CommandRoots::new(cwd, home))
+}
+
+/// Like [`search_commands`], but searching the commands in `roots`.
+pub fn search_commands_in(query: &str, roots: &CommandRoots) -> Vec<DiscoveredCommand> {
+    search(query, discover_commands(roots), |path| {
+        read_command_file(path, MAX_COMMAND_FILE_BYTES)
+            .ok()
+            .flatten()
+    })
+}
+
+impl CommandCache {
+    /// Like [`search_commands_in`], but reads bodies through the cache.
+    pub fn search_commands(&mut self, query: &str, roots: &CommandRoots) -> Vec<DiscoveredCommand> {
+        search(query, discover_commands(roots), |path| {
+            self.body(path).map(str::to_string)
+        })
+    }
+}
+
+/// How well a command matched; lower is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    ExactName,
+    NamePrefix,
+    NameSubstring,
+    Body,
+}
+
+fn search(
+    query: &str,
+    commands: Vec<DiscoveredCommand>,
+    mut read_body: impl FnMut(&Path) -> Option<String>,
+) -> Vec<DiscoveredCommand> {
+    let query = command_key(query.trim());
+    if query.is_empty() {
+        return commands;
+    }
+
+    let mut matches: Vec<(Rank, DiscoveredCommand)> = Vec::new();
+    for command in commands {
+        let name = &command.key;
+        let rank = if *name == query {
+            Some(Rank::ExactName)
+        } else if name.starts_with(&query) {
+            Some(Rank::NamePrefix)
+        } else if name.contains(&query) {
+            Some(Rank::NameSubstring)
+        } else {
+            read_body(&command.path)
+                .filter(|body| body.to_lowercase().contains(&query))
+                .map(|_| Rank::Body)
+        };
+        if let Some(rank) = rank {
+            matches.push((rank, command));
+        }
+    }
+
+    // `sort_by_key` is stable, so discovery order is kept within a rank.
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches.into_iter().map(|(_, command)| command).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn names(commands: &[DiscoveredCommand]) -> Vec<String> {
+        commands.iter().map(DiscoveredCommand::full_name).collect()
+    }
+
+    #[test]
+    fn matches_term_present_only_in_body() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Resolve the failing Migration.");
+        write_command(cwd.path(), "review.md", "Look over the diff.");
+
+        let results = search_commands_with_home("migration", cwd.path(), None);
+        assert_eq!(names(&results), vec!["project:fix".to_string()]);
+    }
+
+    #[test]
+    fn name_matches_rank_before_body_matches() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "deploy.md", "Ship it.");
+        write_command(cwd.path(), "notes.md", "How to Review a PR.");
+        write_command(cwd.path(), "pre-review.md", "Checklist.");
+        write_command(home.path(), "review.md", "Look over the diff.");
+        write_command(cwd.path(), "review__security.md", "Audit.");
+
+        let results = search_commands_with_home("review", cwd.path(), Some(home.path()));
+        assert_eq!(
+            names(&results),
+            vec![
+                "user:review".to_string(),
+                "project:review__security".to_string(),
+                "project:pre-review".to_string(),
+                "project:notes".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_match_case_insensitively_but_keep_their_case() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "Review/Security.md", "Audit.");
+
+        for query in ["security", "REVIEW__SEC", "Review__Security"] {
+            let results = search_commands_with_home(query, cwd.path(), None);
+            assert_eq!(
+                names(&results),
+                vec!["project:Review__Security".to_string()],
+                "{query}"
+            );
+            assert_eq!(results[0].key, "review__security");
+        }
+    }
+
+    #[test]
+    fn cached_search_reads_each_body_once() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "mentions widgets");
+        let roots = CommandRoots::new(cwd.path(), None);
+        let mut cache = CommandCache::new();
+
+        let first = cache.search_commands("widgets", &roots);
+        assert_eq!(names(&first), vec!["project:fix".to_string()]);
+
+        // The cached body is used even though the file changed on disk.
+        fs::write(cwd.path().join(".codex/commands/fix.md"), "changed").unwrap();
+        let second = cache.search_commands("widgets", &roots);
+        assert_eq!(names(&second), vec!["project:fix".to_string()]);
+        assert!(search_commands_with_home("widgets", cwd.path(), None).is_empty());
+    }
+}
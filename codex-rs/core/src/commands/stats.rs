@@ -0,0 +1,133 @@
+//! Command counts for status lines and scope hints.
+
+use std::path::Path;
+
+use super::CommandCache;
+use super::CommandRoots;
+use super::DiscoveredCommand;
+use super::Scope;
+use super::discover::discover_commands;
+
+/// Number of discovered commands in each scope.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub project_count: usize,
+    pub user_count: usize,
+}
+
+impl CommandStats {
+    fn from_commands(commands: &[DiscoveredCommand]) -> Self {
+        let mut stats = Self::default();
+        for command in commands {
+            match command.scope {
+                Scope::Project => stats.project_count += 1,
+                Scope::User => stats.user_count += 1,
+            }
+        }
+        stats
+    }
+
+    fn count(&self, scope: Scope) -> usize {
+        match scope {
+            Scope::Project => self.project_count,
+            Scope::User => self.user_count,
+        }
+    }
+
+    /// The scopes with at least one command, in [`Scope::ALL`] order.
+    fn available_scopes(&self) -> Vec<Scope> {
+        Scope::ALL
+            .into_iter()
+            .filter(|scope| self.count(*scope) > 0)
+            .collect()
+    }
+}
+
+/// Count the commands in `roots`, as listed by discovery.
+pub fn command_stats(roots: &CommandRoots) -> CommandStats {
+    CommandStats::from_commands(&discover_commands(roots))
+}
+
+/// The scopes that have at least one command from `cwd`, with the user scope
+/// resolved against `home` (`None` disables it), e.g. so that a hint bar
+/// only offers `/user:` when there are user commands.
+pub fn available_scopes(cwd: &Path, home: Option<&Path>) -> Vec<Scope> {
+    available_scopes_in(&CommandRoots::new(cwd, home))
+}
+
+/// Like [`available_scopes`], but with the commands in `roots`.
+pub fn available_scopes_in(roots: &CommandRoots) -> Vec<Scope> {
+    command_stats(roots).available_scopes()
+}
+
+impl CommandCache {
+    /// Like [`command_stats`], but walks the command directories only once
+    /// per set of roots.
+    pub fn command_stats(&mut self, roots: &CommandRoots) -> CommandStats {
+        CommandStats::from_commands(self.commands(roots))
+    }
+
+    /// Like [`available_scopes_in`], but walks the command directories only
+    /// once per set of roots.
+    pub fn available_scopes(&mut self, roots: &CommandRoots) -> Vec<Scope> {
+        self.command_stats(roots).available_scopes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn counts_commands_per_scope() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(cwd.path(), "review/security.md", "");
+        write_command(cwd.path(), "_partial.md", "");
+        write_command(cwd.path(), "config.toml", "");
+        write_command(home.path(), "hello.md", "");
+        let roots = CommandRoots::new(cwd.path(), Some(home.path()));
+
+        let expected = CommandStats {
+            project_count: 2,
+            user_count: 1,
+        };
+        assert_eq!(command_stats(&roots), expected);
+
+        let mut cache = CommandCache::new();
+        assert_eq!(cache.command_stats(&roots), expected);
+        // Cached counts do not pick up new files.
+        write_command(cwd.path(), "new.md", "");
+        assert_eq!(cache.command_stats(&roots), expected);
+        assert_eq!(command_stats(&roots).project_count, 3);
+    }
+
+    #[test]
+    fn only_scopes_with_commands_are_available() {
+        let cwd = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "");
+        write_command(home.path(), "_partial.md", "");
+        assert_eq!(
+            available_scopes(cwd.path(), Some(home.path())),
+            vec![Scope::Project]
+        );
+
+        let other = TempDir::new().unwrap();
+        write_command(home.path(), "hello.md", "");
+        assert_eq!(
+            available_scopes(other.path(), Some(home.path())),
+            vec![Scope::User]
+        );
+        assert_eq!(
+            available_scopes(cwd.path(), Some(home.path())),
+            vec![Scope::Project, Scope::User]
+        );
+        assert_eq!(available_scopes(other.path(), None), Vec::new());
+    }
+}
@@ -0,0 +1,185 @@
+//! Checking every command without expanding it for a model, e.g. for a
+//! `codex commands check` workflow.
+
+use std::path::Path;
+
+use super::CommandRoots;
+use super::DiscoveredCommand;
+use super::ExpandError;
+use super::config_file::load_commands_config;
+use super::discover::discover_commands;
+use super::expand::canonical_user_root;
+use super::expand::resolve_includes;
+use super::frontmatter::FrontmatterError;
+use super::frontmatter::split_frontmatter;
+use super::frontmatter::split_frontmatter_checked;
+use super::lint::Lint;
+use super::lint::lint_command;
+use super::read_command_file;
+
+/// The problems found in one command by [`validate_all`].
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub command: DiscoveredCommand,
+    /// Empty when the command is valid.
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum ValidationIssue {
+    /// A placeholder that will not be expanded (see [`lint_command`]).
+    Lint(Lint),
+    /// A malformed frontmatter line or block.
+    InvalidFrontmatter(FrontmatterError),
+    /// The command cannot be expanded, e.g. because the file is too large or
+    /// an `@include` does not resolve.
+    Expand(ExpandError),
+}
+
+/// Validate every command available from `project_root`, with `home` for
+/// the user scope (`None` disables it). Returns one report per discovered
+/// command, in discovery order. Nothing is sent to a model and placeholders
+/// are not substituted.
+pub fn validate_all(project_root: &Path, home: Option<&Path>) -> Vec<ValidationReport> {
+    validate_all_in(&CommandRoots::new(project_root, home))
+}
+
+/// Like [`validate_all`], but validating the commands in `roots`.
+pub fn validate_all_in(roots: &CommandRoots) -> Vec<ValidationReport> {
+    let user_root = canonical_user_root(roots);
+    discover_commands(roots)
+        .into_iter()
+        .map(|command| ValidationReport {
+            issues: validate_command(&command, user_root.as_deref()),
+            command,
+        })
+        .collect()
+}
+
+fn validate_command(command: &DiscoveredCommand, user_root: Option<&Path>) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if let Err(e) = check_command(command, user_root, &mut issues) {
+        issues.push(ValidationIssue::Expand(e));
+    }
+    issues
+}
+
+fn check_command(
+    command: &DiscoveredCommand,
+    user_root: Option<&Path>,
+    issues: &mut Vec<ValidationIssue>,
+) -> Result<(), ExpandError> {
+    let depth = command.relative_path.components().count();
+    let Some(root) = command.path.ancestors().nth(depth) else {
+        return Ok(());
+    };
+    #[cfg(feature = "commands-archive")]
+    if root.is_file() {
+        // Partials are not read from archives, so only the file itself is
+        // checked.
+        let found = super::archive::CommandArchive::open(root)?
+            .find(&command.name)?
+            .ok_or_else(|| ExpandError::FileNotFound(command.path.clone()))?;
+        check_contents(&found.contents, issues);
+        return Ok(());
+    }
+    let config = load_commands_config(root)?;
+    let contents = read_command_file(&command.path, config.max_file_bytes)?
+        .ok_or_else(|| ExpandError::FileNotFound(command.path.clone()))?;
+
+    check_contents(&contents, issues);
+
+    let root = root
+        .canonicalize()
+        .map_err(|e| ExpandError::Io(root.to_path_buf(), e))?;
+    let file = command
+        .path
+        .canonicalize()
+        .map_err(|e| ExpandError::Io(command.path.clone(), e))?;
+    let (frontmatter, body) = split_frontmatter(&contents);
+    let body = frontmatter.template.as_deref().unwrap_or(body);
+    resolve_includes(
+        body,
+        &file,
+        &root,
+        user_root,
+        &config,
+        &mut vec![file.clone()],
+        None,
+        None,
+    )?;
+    Ok(())
+}
+
+/// Report the frontmatter errors and lints of a command file's contents.
+fn check_contents(contents: &str, issues: &mut Vec<ValidationIssue>) {
+    let (_, _, errors) = split_frontmatter_checked(contents);
+    issues.extend(errors.into_iter().map(ValidationIssue::InvalidFrontmatter));
+    issues.extend(
+        lint_command(contents)
+            .into_iter()
+            .map(ValidationIssue::Lint),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn reports_issues_per_command() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "_context.md", "Context for $ARGUMENTS");
+        write_command(
+            cwd.path(),
+            "good.md",
+            "Fix $ARGUMENTS\n@include _context.md\n",
+        );
+        write_command(
+            cwd.path(),
+            "bad.md",
+            "---\nnext = review\n---\nFix $ARGS\n@include _missing.md\n",
+        );
+
+        let reports = validate_all(cwd.path(), None);
+        let names: Vec<(&str, bool)> = reports
+            .iter()
+            .map(|report| (report.command.name.as_str(), report.is_valid()))
+            .collect();
+        assert_eq!(names, vec![("bad", false), ("good", true)]);
+
+        let issues = &reports[0].issues;
+        assert_eq!(issues.len(), 3, "{issues:?}");
+        assert!(matches!(&issues[0], ValidationIssue::InvalidFrontmatter(e) if e.line == 2));
+        assert!(matches!(&issues[1], ValidationIssue::Lint(lint) if lint.line == 4));
+        assert!(matches!(
+            &issues[2],
+            ValidationIssue::Expand(ExpandError::IncludeNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_files_are_reported() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "config.toml", "max_file_bytes = 4\n");
+        write_command(cwd.path(), "long.md", "longer than four bytes");
+
+        let reports = validate_all(cwd.path(), None);
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(
+            reports[0].issues.as_slice(),
+            [ValidationIssue::Expand(ExpandError::FileTooLarge(_, 4))]
+        ));
+    }
+}
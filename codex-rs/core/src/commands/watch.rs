@@ -0,0 +1,151 @@
+//! Watching the commands directories for changes, e.g. to refresh
+//! completions while commands are being edited. Enabled by the
+//! `commands-watch` feature.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+
+use notify::Event;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
+use notify::event::ModifyKind;
+
+use super::CommandRoots;
+use super::ExpandError;
+use super::Scope;
+use super::discover::relative_command_name;
+
+/// A command file that was added, removed or modified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandChange {
+    pub kind: CommandChangeKind,
+    pub scope: Scope,
+    /// The name the file is invoked as, as in discovery.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandChangeKind {
+    /// The file was created, or renamed to a command file.
+    Added,
+    /// The file was deleted, or renamed away.
+    Removed,
+    /// The file's contents or metadata changed.
+    Modified,
+}
+
+/// Watch the commands directories of `project_root` and, when given, `home`
+/// (see [`watch_in`]).
+pub fn watch(
+    project_root: &Path,
+    home: Option<&Path>,
+) -> Result<Receiver<CommandChange>, ExpandError> {
+    watch_in(&CommandRoots::new(project_root, home))
+}
+
+/// Watch every commands directory in `roots` and send a [`CommandChange`]
+/// for each change to a command file beneath one. Partials, group
+/// descriptions and other files that are not commands are ignored. A change
+/// is reported for the file that changed, even if a directory with higher
+/// precedence shadows it, and a single edit may be reported more than once,
+/// so callers should treat changes as a signal to invalidate their
+/// [`CommandCache`](super::CommandCache) and discover again.
+///
+/// Only directories that exist when this is called are watched. Watching
+/// stops once the receiver is dropped and the next change comes in.
+pub fn watch_in(roots: &CommandRoots) -> Result<Receiver<CommandChange>, ExpandError> {
+    let dirs: Vec<(Scope, PathBuf)> = Scope::ALL
+        .into_iter()
+        .flat_map(|scope| roots.dirs(scope).iter().map(move |dir| (scope, dir)))
+        // Notifications name canonical paths on some platforms.
+        .filter_map(|(scope, dir)| Some((scope, dir.canonicalize().ok()?)))
+        .collect();
+
+    let (events_tx, events) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events_tx)
+        .map_err(|e| ExpandError::Watch(roots.cwd().to_path_buf(), e))?;
+    for (_, dir) in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| ExpandError::Watch(dir.clone(), e))?;
+    }
+
+    let (changes_tx, changes) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        // Dropping the watcher when this thread ends stops the watch.
+        let _watcher = watcher;
+        for event in events.into_iter().flatten() {
+            for change in command_changes(&event, &dirs) {
+                if changes_tx.send(change).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(changes)
+}
+
+/// The changes to command files that `event` describes.
+fn command_changes(event: &Event, dirs: &[(Scope, PathBuf)]) -> Vec<CommandChange> {
+    event
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let kind = match event.kind {
+                EventKind::Create(_) => CommandChangeKind::Added,
+                EventKind::Remove(_) => CommandChangeKind::Removed,
+                // A rename is reported for both the old and the new path.
+                EventKind::Modify(ModifyKind::Name(_)) if path.exists() => CommandChangeKind::Added,
+                EventKind::Modify(ModifyKind::Name(_)) => CommandChangeKind::Removed,
+                EventKind::Modify(_) => CommandChangeKind::Modified,
+                _ => return None,
+            };
+            let (scope, name) = dirs.iter().find_map(|(scope, dir)| {
+                Some((*scope, relative_command_name(path.strip_prefix(dir).ok()?)?))
+            })?;
+            Some(CommandChange {
+                kind,
+                scope,
+                name,
+                path: path.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::commands::test_support::write_command;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn adding_a_command_sends_an_added_change() {
+        let cwd = TempDir::new().unwrap();
+        write_command(cwd.path(), "fix.md", "Fix");
+        let changes = watch(cwd.path(), None).unwrap();
+
+        write_command(cwd.path(), "review/_partial.md", "Partial");
+        let path = write_command(cwd.path(), "review/security.md", "Audit");
+
+        let change = std::iter::from_fn(|| changes.recv_timeout(Duration::from_secs(10)).ok())
+            .find(|change| change.kind == CommandChangeKind::Added)
+            .unwrap();
+        assert_eq!(
+            change,
+            CommandChange {
+                kind: CommandChangeKind::Added,
+                scope: Scope::Project,
+                name: "review__security".to_string(),
+                path: path.canonicalize().unwrap(),
+            }
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::config_profile::ConfigProfile;
+use crate::config_types::CustomCommands;
 use crate::config_types::History;
 use crate::config_types::McpServerConfig;
 use crate::config_types::ReasoningEffort;
@@ -132,6 +133,9 @@ pub struct Config {
     /// Collection of settings that are specific to the TUI.
     pub tui: Tui,
 
+    /// Settings for custom slash commands.
+    pub custom_commands: CustomCommands,
+
     /// Path to the `codex-linux-sandbox` executable. This must be set if
     /// [`crate::exec::SandboxType::LinuxSeccomp`] is used. Note that this
     /// cannot be set in the config file: it must be set in code via
@@ -381,6 +385,9 @@ pub struct ConfigToml {
     /// Collection of settings that are specific to the TUI.
     pub tui: Option<Tui>,
 
+    /// Settings for custom slash commands.
+    pub custom_commands: Option<CustomCommands>,
+
     /// When set to `true`, `AgentReasoning` events will be hidden from the
     /// UI/output. Defaults to `false`.
     pub hide_agent_reasoning: Option<bool>,
@@ -642,6 +649,7 @@ impl Config {
             history,
             file_opener: cfg.file_opener.unwrap_or(UriBasedFileOpener::VsCode),
             tui: cfg.tui.unwrap_or_default(),
+            custom_commands: cfg.custom_commands.unwrap_or_default(),
             codex_linux_sandbox_exe,
 
             hide_agent_reasoning: cfg.hide_agent_reasoning.unwrap_or(false),
@@ -810,6 +818,28 @@ persistence = "none"
         );
     }
 
+    #[test]
+    fn test_custom_commands_parsing() {
+        let cfg = toml::from_str::<ConfigToml>("[custom_commands]\n")
+            .expect("TOML deserialization should succeed");
+        assert_eq!(Some(CustomCommands::default()), cfg.custom_commands);
+        assert!(CustomCommands::default().allow_user_scope);
+
+        let locked_down = r#"
+[custom_commands]
+allow_user_scope = false
+"#;
+        let cfg =
+            toml::from_str::<ConfigToml>(locked_down).expect("TOML deserialization should succeed");
+        assert_eq!(
+            Some(CustomCommands {
+                user_dirs: None,
+                allow_user_scope: false,
+            }),
+            cfg.custom_commands
+        );
+    }
+
     #[test]
     fn test_sandbox_config_parsing() {
         let sandbox_full_access = r#"
@@ -1020,6 +1050,7 @@ disable_response_storage = true
                 history: History::default(),
                 file_opener: UriBasedFileOpener::VsCode,
                 tui: Tui::default(),
+                custom_commands: CustomCommands::default(),
                 codex_linux_sandbox_exe: None,
                 hide_agent_reasoning: false,
                 show_raw_agent_reasoning: false,
@@ -1072,6 +1103,7 @@ disable_response_storage = true
             history: History::default(),
             file_opener: UriBasedFileOpener::VsCode,
             tui: Tui::default(),
+            custom_commands: CustomCommands::default(),
             codex_linux_sandbox_exe: None,
             hide_agent_reasoning: false,
             show_raw_agent_reasoning: false,
@@ -1139,6 +1171,7 @@ disable_response_storage = true
             history: History::default(),
             file_opener: UriBasedFileOpener::VsCode,
             tui: Tui::default(),
+            custom_commands: CustomCommands::default(),
             codex_linux_sandbox_exe: None,
             hide_agent_reasoning: false,
             show_raw_agent_reasoning: false,
@@ -78,6 +78,34 @@ pub enum HistoryPersistence {
 #[derive(Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Tui {}
 
+/// Settings for custom slash commands loaded from `.codex/commands`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomCommands {
+    /// Ordered list of user-scope commands directories. Earlier directories
+    /// win when several define the same command. Defaults to
+    /// `~/.codex/commands`.
+    #[serde(default)]
+    pub user_dirs: Option<Vec<PathBuf>>,
+
+    /// When false, `/user:` commands are rejected and user-scope commands
+    /// are not listed. Defaults to true.
+    #[serde(default = "default_allow_user_scope")]
+    pub allow_user_scope: bool,
+}
+
+impl Default for CustomCommands {
+    fn default() -> Self {
+        Self {
+            user_dirs: None,
+            allow_user_scope: default_allow_user_scope(),
+        }
+    }
+}
+
+fn default_allow_user_scope() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum SandboxMode {
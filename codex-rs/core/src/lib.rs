@@ -14,6 +14,7 @@ pub mod codex;
 pub use codex::Codex;
 pub use codex::CodexSpawnOk;
 pub mod codex_wrapper;
+pub mod commands;
 pub mod config;
 pub mod config_profile;
 pub mod config_types;
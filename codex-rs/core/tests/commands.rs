@@ -0,0 +1,125 @@
+//! Exercises `codex_core::commands` through its public API only.
+
+#![allow(clippy::unwrap_used)]
+
+use std::fs;
+use std::path::Path;
+
+use codex_core::commands::CommandRoots;
+use codex_core::commands::ExpandError;
+use codex_core::commands::PROJECT_COMMANDS_DIR;
+use codex_core::commands::Scope;
+use codex_core::commands::USER_COMMANDS_DIR;
+use codex_core::commands::discover_custom_commands_in;
+use codex_core::commands::discover_custom_commands_with_home;
+use codex_core::commands::expand;
+use codex_core::commands::expand_custom_command_in;
+use codex_core::commands::expand_custom_command_with_home;
+use tempfile::TempDir;
+
+fn write_command(root: &Path, relative: &str, contents: &str) {
+    let path = root.join(PROJECT_COMMANDS_DIR).join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn project_and_user_commands_expand_and_are_discovered() {
+    let project = TempDir::new().unwrap();
+    let home = TempDir::new().unwrap();
+    write_command(
+        project.path(),
+        "review/security.md",
+        "Audit $1 for $ARGUMENTS",
+    );
+    write_command(home.path(), "greet.md", "Say hi to $ARGUMENTS");
+
+    assert_eq!(
+        discover_custom_commands_with_home(project.path(), Some(home.path())),
+        vec![
+            "project:review__security".to_string(),
+            "user:greet".to_string(),
+        ]
+    );
+
+    let prompt = expand_custom_command_with_home(
+        "/project:review__security api",
+        project.path(),
+        Some(home.path()),
+    )
+    .unwrap()
+    .prompt;
+    assert_eq!(prompt, "Audit api for api");
+
+    let prompt =
+        expand_custom_command_with_home("/user:greet everyone", project.path(), Some(home.path()))
+            .unwrap()
+            .prompt;
+    assert_eq!(prompt, "Say hi to everyone");
+}
+
+#[test]
+fn commands_expand_from_scope_and_name() {
+    let project = TempDir::new().unwrap();
+    write_command(project.path(), "fix.md", "Fix $ARGUMENTS via $INPUT");
+
+    let prompt = expand(Scope::Project, "fix", "the tests", project.path())
+        .unwrap()
+        .prompt;
+    assert_eq!(prompt, "Fix the tests via /project:fix the tests");
+}
+
+#[test]
+fn errors_are_reported_through_the_public_error_type() {
+    let project = TempDir::new().unwrap();
+
+    let err = expand_custom_command_with_home("fix", project.path(), None).unwrap_err();
+    assert!(matches!(err, ExpandError::NotACommand));
+
+    let err = expand_custom_command_with_home("/project:fix", project.path(), None).unwrap_err();
+    assert!(matches!(err, ExpandError::NoCommandsDir(_)));
+
+    write_command(project.path(), "lint.md", "Lint");
+    let err = expand_custom_command_with_home("/project:fix", project.path(), None).unwrap_err();
+    assert!(matches!(err, ExpandError::FileNotFound(_)));
+
+    // Without a home directory the user scope is unavailable.
+    let err = expand_custom_command_with_home("/user:fix", project.path(), None).unwrap_err();
+    assert!(matches!(err, ExpandError::UnknownScope(scope) if scope == "user"));
+}
+
+#[test]
+fn scope_prefixes_round_trip() {
+    for scope in Scope::ALL {
+        assert_eq!(Scope::from_prefix(scope.as_str()), Some(scope));
+    }
+    assert_eq!(Scope::from_prefix("team"), None);
+}
+
+#[test]
+fn discovery_and_expansion_share_the_public_layout() {
+    let project = TempDir::new().unwrap();
+    let home = TempDir::new().unwrap();
+    let project_dir = project.path().join(PROJECT_COMMANDS_DIR);
+    let user_dir = home.path().join(USER_COMMANDS_DIR);
+    fs::create_dir_all(project_dir.join("Review")).unwrap();
+    fs::create_dir_all(&user_dir).unwrap();
+    fs::write(project_dir.join("Review/Fix.md"), "project fix").unwrap();
+    fs::write(user_dir.join("Greet.md"), "user greet").unwrap();
+
+    let roots = CommandRoots::new(project.path(), Some(home.path()));
+    assert_eq!(roots.dirs(Scope::Project), [project_dir]);
+    assert_eq!(roots.dirs(Scope::User), [user_dir]);
+    assert_eq!(
+        discover_custom_commands_in(&roots),
+        vec!["project:Review__Fix".to_string(), "user:Greet".to_string()]
+    );
+    for (input, prompt) in [
+        ("/project:Review__Fix", "project fix"),
+        ("/project:review__fix", "project fix"),
+        ("/user:greet", "user greet"),
+    ] {
+        let expanded = expand_custom_command_in(input, &roots).unwrap();
+        assert_eq!(expanded.prompt, prompt, "{input}");
+    }
+}
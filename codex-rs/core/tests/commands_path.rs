@@ -0,0 +1,40 @@
+//! `CODEX_COMMANDS_PATH` as seen by the entry points that read the
+//! environment. This lives in its own test binary because it sets process
+//! environment variables.
+
+#![allow(clippy::unwrap_used)]
+
+use std::fs;
+
+use codex_core::commands::COMMANDS_PATH_ENV_VAR;
+use codex_core::commands::discover_custom_commands;
+use codex_core::commands::expand_custom_command;
+use tempfile::TempDir;
+
+#[test]
+fn commands_path_dirs_are_discovered_and_expanded() {
+    let cwd = TempDir::new().unwrap();
+    let home = TempDir::new().unwrap();
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    fs::write(first.path().join("deploy.md"), "Deploy $ARGUMENTS").unwrap();
+    fs::write(second.path().join("deploy.md"), "shadowed").unwrap();
+    fs::write(second.path().join("lint.md"), "Lint $1").unwrap();
+
+    let path = std::env::join_paths([first.path(), second.path()]).unwrap();
+    // SAFETY: this is the only test in this binary, so no other thread reads
+    // the environment concurrently.
+    unsafe {
+        std::env::set_var("HOME", home.path());
+        std::env::set_var(COMMANDS_PATH_ENV_VAR, path);
+    }
+
+    assert_eq!(
+        discover_custom_commands(cwd.path()),
+        vec!["user:deploy".to_string(), "user:lint".to_string()]
+    );
+    let expanded = expand_custom_command("/user:deploy api", cwd.path()).unwrap();
+    assert_eq!(expanded.prompt, "Deploy api");
+    let expanded = expand_custom_command("/user:lint src", cwd.path()).unwrap();
+    assert_eq!(expanded.prompt, "Lint src");
+}
@@ -21,6 +21,7 @@ use ratatui::widgets::StatefulWidgetRef;
 use ratatui::widgets::WidgetRef;
 
 use super::chat_composer_history::ChatComposerHistory;
+use super::command_popup::CommandItem;
 use super::command_popup::CommandPopup;
 use super::file_search_popup::FileSearchPopup;
 
@@ -61,6 +62,9 @@ pub(crate) struct ChatComposer {
     pending_pastes: Vec<(String, String)>,
     token_usage_info: Option<TokenUsageInfo>,
     has_focus: bool,
+    /// Custom commands (as `scope:name`) offered alongside the built-ins in
+    /// the slash-command popup.
+    custom_commands: Vec<String>,
 }
 
 /// Popup state – at most one can be visible at any time.
@@ -91,6 +95,7 @@ impl ChatComposer {
             pending_pastes: Vec::new(),
             token_usage_info: None,
             has_focus: has_input_focus,
+            custom_commands: Vec::new(),
         }
     }
 
@@ -139,6 +144,11 @@ impl ChatComposer {
         });
     }
 
+    /// Set the custom commands offered in the slash-command popup.
+    pub(crate) fn set_custom_commands(&mut self, custom_commands: Vec<String>) {
+        self.custom_commands = custom_commands;
+    }
+
     /// Record the history metadata advertised by `SessionConfiguredEvent` so
     /// that the composer can navigate cross-session history.
     pub(crate) fn set_history_metadata(&mut self, log_id: u64, entry_count: usize) {
@@ -258,18 +268,41 @@ impl ChatComposer {
                 modifiers: KeyModifiers::NONE,
                 ..
             } => {
-                if let Some(cmd) = popup.selected_command() {
-                    // Send command to the app layer.
-                    self.app_event_tx.send(AppEvent::DispatchCommand(*cmd));
+                match popup.selected_command() {
+                    Some(CommandItem::Builtin(cmd)) => {
+                        // Send command to the app layer.
+                        self.app_event_tx.send(AppEvent::DispatchCommand(cmd));
 
-                    // Clear textarea so no residual text remains.
-                    self.textarea.set_text("");
+                        // Clear textarea so no residual text remains.
+                        self.textarea.set_text("");
 
-                    // Hide popup since the command has been dispatched.
-                    self.active_popup = ActivePopup::None;
-                    return (InputResult::None, true);
+                        // Hide popup since the command has been dispatched.
+                        self.active_popup = ActivePopup::None;
+                        return (InputResult::None, true);
+                    }
+                    Some(CommandItem::Custom(name)) => {
+                        // Custom commands are expanded when the message is
+                        // submitted, so complete the command name (keeping any
+                        // arguments already typed) and submit as usual.
+                        let name = name.to_string();
+                        let text = self.textarea.text();
+                        let args = text
+                            .trim_start()
+                            .split_once(char::is_whitespace)
+                            .map(|(_, args)| args.trim_start())
+                            .unwrap_or("");
+                        let completed = if args.is_empty() {
+                            format!("/{name}")
+                        } else {
+                            format!("/{name} {args}")
+                        };
+                        self.textarea.set_text(&completed);
+                        self.active_popup = ActivePopup::None;
+                    }
+                    None => {}
                 }
-                // Fallback to default newline handling if no command selected.
+                // Submit custom commands, or fall back to default newline
+                // handling if no command is selected.
                 self.handle_key_event_without_popup(key_event)
             }
             input => self.handle_input_basic(input),
@@ -592,7 +625,7 @@ impl ChatComposer {
             }
             _ => {
                 if input_starts_with_slash {
-                    let mut command_popup = CommandPopup::new();
+                    let mut command_popup = CommandPopup::new(self.custom_commands.clone());
                     command_popup.on_composer_text_change(first_line.to_string());
                     self.active_popup = ActivePopup::Command(command_popup);
                 }
@@ -10,17 +10,44 @@ use crate::slash_command::SlashCommand;
 use crate::slash_command::built_in_slash_commands;
 use codex_common::fuzzy_match::fuzzy_match;
 
+/// An entry in the popup: either a built-in command or a custom command
+/// discovered on disk (identified by its `scope:name` string).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CommandItem<'a> {
+    Builtin(SlashCommand),
+    Custom(&'a str),
+}
+
+impl CommandItem<'_> {
+    /// Command string without the leading '/'.
+    pub(crate) fn command(&self) -> &str {
+        match self {
+            CommandItem::Builtin(cmd) => cmd.command(),
+            CommandItem::Custom(name) => name,
+        }
+    }
+
+    fn description(&self) -> Option<String> {
+        match self {
+            CommandItem::Builtin(cmd) => Some(cmd.description().to_string()),
+            CommandItem::Custom(_) => Some("custom command".to_string()),
+        }
+    }
+}
+
 pub(crate) struct CommandPopup {
     command_filter: String,
     all_commands: Vec<(&'static str, SlashCommand)>,
+    custom_commands: Vec<String>,
     state: ScrollState,
 }
 
 impl CommandPopup {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(custom_commands: Vec<String>) -> Self {
         Self {
             command_filter: String::new(),
             all_commands: built_in_slash_commands(),
+            custom_commands,
             state: ScrollState::new(),
         }
     }
@@ -64,17 +91,26 @@ impl CommandPopup {
 
     /// Compute fuzzy-filtered matches paired with optional highlight indices and score.
     /// Sorted by ascending score, then by command name for stability.
-    fn filtered(&self) -> Vec<(&SlashCommand, Option<Vec<usize>>, i32)> {
+    fn filtered(&self) -> Vec<(CommandItem<'_>, Option<Vec<usize>>, i32)> {
         let filter = self.command_filter.trim();
-        let mut out: Vec<(&SlashCommand, Option<Vec<usize>>, i32)> = Vec::new();
+        let items = self
+            .all_commands
+            .iter()
+            .map(|(_, cmd)| CommandItem::Builtin(*cmd))
+            .chain(
+                self.custom_commands
+                    .iter()
+                    .map(|name| CommandItem::Custom(name.as_str())),
+            );
+        let mut out: Vec<(CommandItem<'_>, Option<Vec<usize>>, i32)> = Vec::new();
         if filter.is_empty() {
-            for (_, cmd) in self.all_commands.iter() {
-                out.push((cmd, None, 0));
+            for item in items {
+                out.push((item, None, 0));
             }
         } else {
-            for (_, cmd) in self.all_commands.iter() {
-                if let Some((indices, score)) = fuzzy_match(cmd.command(), filter) {
-                    out.push((cmd, Some(indices), score));
+            for item in items {
+                if let Some((indices, score)) = fuzzy_match(item.command(), filter) {
+                    out.push((item, Some(indices), score));
                 }
             }
         }
@@ -82,7 +118,7 @@ impl CommandPopup {
         out
     }
 
-    fn filtered_commands(&self) -> Vec<&SlashCommand> {
+    fn filtered_commands(&self) -> Vec<CommandItem<'_>> {
         self.filtered().into_iter().map(|(c, _, _)| c).collect()
     }
 
@@ -104,7 +140,7 @@ impl CommandPopup {
     }
 
     /// Return currently selected command, if any.
-    pub(crate) fn selected_command(&self) -> Option<&SlashCommand> {
+    pub(crate) fn selected_command(&self) -> Option<CommandItem<'_>> {
         let matches = self.filtered_commands();
         self.state
             .selected_idx
@@ -124,7 +160,7 @@ impl WidgetRef for CommandPopup {
                     name: format!("/{}", cmd.command()),
                     match_indices: indices.map(|v| v.into_iter().map(|i| i + 1).collect()),
                     is_current: false,
-                    description: Some(cmd.description().to_string()),
+                    description: cmd.description(),
                 })
                 .collect()
         };
@@ -138,7 +174,7 @@ mod tests {
 
     #[test]
     fn filter_includes_init_when_typing_prefix() {
-        let mut popup = CommandPopup::new();
+        let mut popup = CommandPopup::new(Vec::new());
         // Simulate the composer line starting with '/in' so the popup filters
         // matching commands by prefix.
         popup.on_composer_text_change("/in".to_string());
@@ -152,9 +188,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn custom_commands_are_listed_and_selectable() {
+        let mut popup = CommandPopup::new(vec!["project:fix".to_string()]);
+        popup.on_composer_text_change("/project:fix".to_string());
+
+        assert_eq!(
+            popup.selected_command(),
+            Some(CommandItem::Custom("project:fix"))
+        );
+    }
+
     #[test]
     fn selecting_init_by_exact_match() {
-        let mut popup = CommandPopup::new();
+        let mut popup = CommandPopup::new(Vec::new());
         popup.on_composer_text_change("/init".to_string());
 
         // When an exact match exists, the selected command should be that
@@ -327,6 +327,11 @@ impl BottomPane<'_> {
         self.app_event_tx.send(AppEvent::RequestRedraw)
     }
 
+    /// Set the custom commands offered in the slash-command popup.
+    pub(crate) fn set_custom_commands(&mut self, custom_commands: Vec<String>) {
+        self.composer.set_custom_commands(custom_commands);
+    }
+
     // --- History helpers ---
 
     pub(crate) fn set_history_metadata(&mut self, log_id: u64, entry_count: usize) {
@@ -45,6 +45,10 @@ use crate::bottom_pane::BottomPane;
 use crate::bottom_pane::BottomPaneParams;
 use crate::bottom_pane::CancellationEvent;
 use crate::bottom_pane::InputResult;
+use crate::custom_slash_command::ExpandError;
+use crate::custom_slash_command::command_roots;
+use crate::custom_slash_command::discover_custom_commands_in;
+use crate::custom_slash_command::expand_custom_command_in;
 use crate::history_cell::CommandOutput;
 use crate::history_cell::HistoryCell;
 use crate::history_cell::PatchEventType;
@@ -200,14 +204,17 @@ impl ChatWidget<'_> {
             }
         });
 
-        Self {
+        let mut bottom_pane = BottomPane::new(BottomPaneParams {
             app_event_tx: app_event_tx.clone(),
+            has_input_focus: true,
+            enhanced_keys_supported,
+        });
+        bottom_pane.set_custom_commands(discover_custom_commands_in(&command_roots(&config)));
+
+        Self {
+            app_event_tx,
             codex_op_tx,
-            bottom_pane: BottomPane::new(BottomPaneParams {
-                app_event_tx,
-                has_input_focus: true,
-                enhanced_keys_supported,
-            }),
+            bottom_pane,
             active_history_cell: None,
             config,
             initial_user_message: create_initial_user_message(
@@ -242,7 +249,9 @@ impl ChatWidget<'_> {
 
         match self.bottom_pane.handle_key_event(key_event) {
             InputResult::Submitted(text) => {
-                self.submit_user_message(text.into());
+                if let Some(text) = self.expand_custom_command_input(text) {
+                    self.submit_user_message(text.into());
+                }
             }
             InputResult::None => {}
         }
@@ -252,6 +261,30 @@ impl ChatWidget<'_> {
         self.bottom_pane.handle_paste(text);
     }
 
+    /// If `text` invokes a custom slash command, replace it with the
+    /// expanded prompt. Returns `None` (after reporting the error) when an
+    /// explicitly scoped command such as `/project:fix` cannot be expanded.
+    fn expand_custom_command_input(&mut self, text: String) -> Option<String> {
+        if !text.starts_with('/') {
+            return Some(text);
+        }
+        match expand_custom_command_in(&text, &command_roots(&self.config)) {
+            Ok(expanded) => Some(expanded.prompt),
+            // A bare `/something` that is not a command file is most likely
+            // regular input (e.g. a path), so send it unchanged.
+            Err(
+                ExpandError::NotACommand
+                | ExpandError::InvalidName(_)
+                | ExpandError::NoCommandsDir(_)
+                | ExpandError::FileNotFound(_),
+            ) if !text.split_whitespace().next().unwrap_or("").contains(':') => Some(text),
+            Err(e) => {
+                self.add_to_history(HistoryCell::new_error_event(e.to_string()));
+                None
+            }
+        }
+    }
+
     fn add_to_history(&mut self, cell: HistoryCell) {
         self.app_event_tx
             .send(AppEvent::InsertHistory(cell.plain_lines()));
@@ -0,0 +1,27 @@
+//! Custom slash commands loaded from `.codex/commands` directories.
+//!
+//! The implementation lives in [`codex_core::commands`] so that it can be
+//! shared with front ends other than the TUI; this module re-exports the
+//! pieces the TUI uses.
+
+use codex_core::commands::CommandRoots;
+use codex_core::config::Config;
+
+pub(crate) use codex_core::commands::ExpandError;
+pub(crate) use codex_core::commands::discover_custom_commands_in;
+pub(crate) use codex_core::commands::expand_custom_command_in;
+
+/// Command roots for `config`, honoring the `[custom_commands]` settings.
+pub(crate) fn command_roots(config: &Config) -> CommandRoots {
+    let settings = &config.custom_commands;
+    let roots = CommandRoots::for_cwd(&config.cwd);
+    let roots = match &settings.user_dirs {
+        Some(dirs) => roots.with_user_dirs(dirs.clone()),
+        None => roots,
+    };
+    if settings.allow_user_scope {
+        roots
+    } else {
+        roots.without_user_scope()
+    }
+}
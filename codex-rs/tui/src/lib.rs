@@ -30,6 +30,7 @@ mod chatwidget;
 mod citation_regex;
 mod cli;
 mod colors;
+mod custom_slash_command;
 pub mod custom_terminal;
 mod exec_command;
 mod file_search;